@@ -0,0 +1,100 @@
+//! # peach-menu-sim
+//!
+//! Desktop simulator for `peach-menu`. Runs the menu state machine without
+//! any PeachCloud microservices: the screen is rendered as ASCII art in the
+//! terminal, network and system data are simulated and button presses are
+//! read from the keyboard.
+//!
+use std::error::Error;
+use std::io::stdout;
+use std::process;
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel::{unbounded, Sender};
+use crossterm::event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal;
+use log::error;
+
+use peach_menu::config::{Buttons, Config};
+use peach_menu::display::Terminal;
+use peach_menu::monitor::Monitor;
+use peach_menu::render::Renderer;
+use peach_menu::services::Simulated;
+use peach_menu::state_machine::{state_changer, Button, Context, Input, State};
+
+const KEYS: &str = "\nArrows: Up Down Left Right   Enter: Center   a: A   b: B   q: Quit\n";
+
+fn main() {
+    // initialize the logger
+    env_logger::init();
+
+    // handle errors returned from `run`
+    if let Err(e) = run() {
+        error!("Application error: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Launches the state machine with a terminal display and simulated services
+/// and feeds it button codes read from the keyboard until `q` is pressed.
+fn run() -> Result<(), Box<dyn Error>> {
+    let config = Config::load()?;
+
+    let buttons = config.buttons.clone();
+    let (s, r) = unbounded();
+    let display = Terminal::new(stdout()).with_caption(KEYS);
+    let display = Renderer::new(display, config.burn_in.clone());
+    let monitor = Monitor::new(&config.network.interfaces);
+    let ctx = Context {
+        config,
+        display: Box::new(display),
+        services: Box::new(Simulated::new()),
+        monitor,
+    };
+
+    terminal::enable_raw_mode()?;
+    let state_machine = state_changer(r, ctx, Arc::new(Mutex::new(State::Logo)));
+    let result = read_keys(&s, &buttons);
+    terminal::disable_raw_mode()?;
+
+    // dropping the sender stops the state machine
+    drop(s);
+    if state_machine.join().is_err() {
+        return Err("state machine thread panicked".into());
+    }
+
+    result
+}
+
+/// Reads key presses and sends the codes which `buttons` maps to the
+/// corresponding buttons to the state machine, as `peach-buttons` would.
+/// Returns when `q`, `Esc` or `Ctrl-C` is pressed.
+fn read_keys(s: &Sender<Input>, buttons: &Buttons) -> Result<(), Box<dyn Error>> {
+    loop {
+        let key = match event::read()? {
+            TermEvent::Key(key) => key,
+            _ => continue,
+        };
+        let button = match key {
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+            } => return Ok(()),
+            KeyEvent { code, .. } => match code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Enter => Button::Center,
+                KeyCode::Left => Button::Left,
+                KeyCode::Right => Button::Right,
+                KeyCode::Up => Button::Up,
+                KeyCode::Down => Button::Down,
+                KeyCode::Char('a') | KeyCode::Char('A') => Button::A,
+                KeyCode::Char('b') | KeyCode::Char('B') => Button::B,
+                _ => continue,
+            },
+        };
+        // the configuration is validated, so every button has a code
+        if let Some(code) = buttons.encode(button) {
+            s.send(Input::Button(code))?;
+        }
+    }
+}
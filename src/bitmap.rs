@@ -0,0 +1,162 @@
+//! Bitmaps built at runtime for `Display::draw`.
+//!
+//! Pixels are stored in rows, most significant bit first, with each row
+//! padded to a whole number of bytes.
+
+use crate::font;
+
+/// A monochrome image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    width: u32,
+    height: u32,
+    bytes: Vec<u8>,
+}
+
+impl Bitmap {
+    /// Returns a bitmap with every pixel unlit.
+    pub fn new(width: u32, height: u32) -> Bitmap {
+        let stride = ((width + 7) / 8) as usize;
+        Bitmap {
+            width,
+            height,
+            bytes: vec![0; stride * height as usize],
+        }
+    }
+
+    /// Returns an unfilled rectangle, used to highlight a key or frame a bar.
+    pub fn frame(width: u32, height: u32) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                    bitmap.set(x, y, true);
+                }
+            }
+        }
+        bitmap
+    }
+
+    /// Returns a framed bar filled from the left to `percent` of its width.
+    pub fn bar(width: u32, height: u32, percent: u32) -> Bitmap {
+        let mut bitmap = Bitmap::frame(width, height);
+        let filled = (width - 2) * percent.min(100) / 100;
+        for y in 1..height - 1 {
+            for x in 1..filled + 1 {
+                bitmap.set(x, y, true);
+            }
+        }
+        bitmap
+    }
+
+    /// Returns `text` in the 6x8 font as unlit characters on a lit
+    /// background, `width` pixels wide.
+    pub fn inverted_text(text: &str, width: u32) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, font::HEIGHT);
+        let chars: Vec<char> = text.chars().collect();
+        for y in 0..font::HEIGHT {
+            for x in 0..width {
+                let lit = match chars.get((x / font::WIDTH) as usize) {
+                    Some(&c) => !font::pixel(c, x % font::WIDTH, y),
+                    None => true,
+                };
+                bitmap.set(x, y, lit);
+            }
+        }
+        bitmap
+    }
+
+    /// Returns `values` as a column chart, one pixel column per value with
+    /// the last value on the right. Columns are scaled so that `max` fills
+    /// the height of the bitmap.
+    pub fn sparkline(values: &[u64], max: u64, width: u32, height: u32) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height);
+        let shown = &values[values.len().saturating_sub(width as usize)..];
+        let max = max.max(1);
+        let left = width - shown.len() as u32;
+        for (i, &value) in shown.iter().enumerate() {
+            // any activity is drawn at least one pixel high
+            let lit = match value {
+                0 => 0,
+                _ => ((value.min(max) * height as u64 + max - 1) / max) as u32,
+            };
+            for y in height - lit..height {
+                bitmap.set(left + i as u32, y, true);
+            }
+        }
+        bitmap
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Sets the pixel at column `x` and row `y`. Pixels outside the bitmap
+    /// are ignored.
+    pub fn set(&mut self, x: u32, y: u32, on: bool) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let stride = (self.width + 7) / 8;
+        let index = (y * stride + x / 8) as usize;
+        let mask = 0x80 >> (x % 8);
+        if on {
+            self.bytes[index] |= mask;
+        } else {
+            self.bytes[index] &= !mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the number of lit pixels in each column of `bitmap`.
+    fn columns(bitmap: &Bitmap) -> Vec<u32> {
+        let stride = (bitmap.width() + 7) / 8;
+        (0..bitmap.width())
+            .map(|x| {
+                (0..bitmap.height())
+                    .filter(|y| {
+                        bitmap.bytes()[(y * stride + x / 8) as usize] & (0x80 >> (x % 8)) != 0
+                    })
+                    .count() as u32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sparkline_is_scaled_to_max_and_aligned_right() {
+        let bitmap = Bitmap::sparkline(&[0, 1, 5, 10], 10, 6, 10);
+        assert_eq!(columns(&bitmap), vec![0, 0, 0, 1, 5, 10]);
+    }
+
+    #[test]
+    fn sparkline_rounds_activity_up_and_clips_at_max() {
+        let bitmap = Bitmap::sparkline(&[1, 50, 200], 100, 3, 8);
+        assert_eq!(columns(&bitmap), vec![1, 4, 8]);
+        let bitmap = Bitmap::sparkline(&[0, 3], 0, 2, 8);
+        assert_eq!(columns(&bitmap), vec![0, 8]);
+    }
+
+    #[test]
+    fn sparkline_shows_the_most_recent_values() {
+        let bitmap = Bitmap::sparkline(&[8, 8, 8, 2, 4], 8, 3, 8);
+        assert_eq!(columns(&bitmap), vec![8, 2, 4]);
+    }
+
+    #[test]
+    fn sparkline_columns_grow_from_the_bottom() {
+        let bitmap = Bitmap::sparkline(&[1], 8, 1, 8);
+        assert_eq!(bitmap.bytes(), &[0, 0, 0, 0, 0, 0, 0, 0x80][..]);
+    }
+}
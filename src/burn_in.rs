@@ -0,0 +1,152 @@
+//! OLED burn-in protection.
+//!
+//! Screens such as the logo and the home menu light the same pixels for days.
+//! Every frame is moved by a small offset which changes periodically, and is
+//! optionally inverted every other period, before it is sent to the display.
+//!
+//! The frame is only moved right and down. Text is drawn from the top-left
+//! corner of the screen, and the bottom row and right column of every glyph
+//! are blank, so a shift of one pixel moves only spacing off the screen.
+
+use crate::config::BurnIn;
+use crate::display::{Framebuffer, HEIGHT, WIDTH};
+
+/// The offset and inversion applied to a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    dx: i32,
+    dy: i32,
+    inverted: bool,
+}
+
+impl Transform {
+    /// Returns the transform for `now`, in seconds since the epoch.
+    ///
+    /// The offset walks back and forth over the square of positions up to
+    /// `shift` pixels right of and below the origin, taking one step every
+    /// `shift_interval` seconds.
+    pub fn at(config: &BurnIn, now: u64) -> Transform {
+        let side = config.shift as u64 + 1;
+        let (dx, dy) = match config.shift_interval {
+            0 => (0, 0),
+            interval => {
+                let step = now / interval % (side * side);
+                let row = step / side;
+                // reverse every other row so that each step moves one pixel
+                let col = if row % 2 == 0 {
+                    step % side
+                } else {
+                    side - 1 - step % side
+                };
+                (col as i32, row as i32)
+            }
+        };
+        let inverted = match config.invert_interval {
+            0 => false,
+            interval => now / interval % 2 == 1,
+        };
+        Transform { dx, dy, inverted }
+    }
+
+    /// Returns `frame` moved and inverted.
+    pub fn apply(&self, frame: &Framebuffer) -> Framebuffer {
+        let mut out = Framebuffer::new();
+        for y in 0..HEIGHT as i32 {
+            for x in 0..WIDTH as i32 {
+                let lit = frame.get(x - self.dx, y - self.dy);
+                out.set(x, y, lit != self.inverted);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+
+    fn burn_in(shift: u32, shift_interval: u64, invert_interval: u64) -> BurnIn {
+        BurnIn {
+            shift,
+            shift_interval,
+            invert_interval,
+        }
+    }
+
+    fn offset(config: &BurnIn, now: u64) -> (i32, i32) {
+        let transform = Transform::at(config, now);
+        (transform.dx, transform.dy)
+    }
+
+    #[test]
+    fn offset_walks_the_square_one_pixel_at_a_time() {
+        let config = burn_in(1, 60, 0);
+        let walk: Vec<_> = (0..5).map(|step| offset(&config, step * 60)).collect();
+        assert_eq!(walk, vec![(0, 0), (1, 0), (1, 1), (0, 1), (0, 0)]);
+        assert_eq!(offset(&config, 59), (0, 0));
+    }
+
+    #[test]
+    fn offsets_are_never_negative() {
+        let config = burn_in(2, 1, 0);
+        for now in 0..9 {
+            let (dx, dy) = offset(&config, now);
+            assert!((0..=2).contains(&dx) && (0..=2).contains(&dy));
+        }
+        assert_eq!(offset(&config, 3), (2, 1));
+    }
+
+    #[test]
+    fn zero_intervals_disable_the_transform() {
+        let transform = Transform::at(&burn_in(1, 0, 0), 12345);
+        assert_eq!(transform, Transform::at(&burn_in(0, 60, 0), 0));
+        assert!(!transform.inverted);
+    }
+
+    #[test]
+    fn frame_is_inverted_every_other_interval() {
+        let config = burn_in(0, 60, 300);
+        assert!(!Transform::at(&config, 299).inverted);
+        assert!(Transform::at(&config, 300).inverted);
+        assert!(!Transform::at(&config, 600).inverted);
+    }
+
+    #[test]
+    fn moved_pixels_past_the_edge_are_dropped() {
+        let mut frame = Framebuffer::new();
+        frame.set(0, 0, true);
+        frame.set(WIDTH as i32 - 1, HEIGHT as i32 - 1, true);
+        let transform = Transform {
+            dx: 1,
+            dy: 1,
+            inverted: false,
+        };
+        let out = transform.apply(&frame);
+        assert!(out.get(1, 1));
+        assert!(!out.get(0, 0));
+        assert!(!out.get(WIDTH as i32 - 1, HEIGHT as i32 - 1));
+    }
+
+    #[test]
+    fn text_keeps_every_lit_pixel_when_moved() {
+        let mut frame = Framebuffer::new();
+        frame.write(0, 0, "N", "6x8").unwrap();
+        frame
+            .write(WIDTH as i32 - 6, HEIGHT as i32 - 8, "g", "6x8")
+            .unwrap();
+        let lit = |frame: &Framebuffer| {
+            let mut count = 0;
+            for y in 0..HEIGHT as i32 {
+                for x in 0..WIDTH as i32 {
+                    count += frame.get(x, y) as usize;
+                }
+            }
+            count
+        };
+        for now in 0..4 {
+            let moved = Transform::at(&burn_in(1, 1, 0), now).apply(&frame);
+            assert_eq!(lit(&moved), lit(&frame));
+        }
+    }
+}
@@ -1,10 +1,78 @@
-use std::process;
+use std::cell::Cell;
+use std::time::Duration;
 
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use ws::{CloseCode, Error, Handler, Handshake, Message, Sender};
 
+use crate::error::MenuError;
+use crate::state_machine::{Button, Input};
+
+/// Delay before the first reconnection attempt to `peach_buttons`.
+const BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+/// Upper bound for the delay between reconnection attempts.
+const BACKOFF_MAX: Duration = Duration::from_secs(32);
+
+/// The button codes of the standard layout.
+const STANDARD: &[Mapping] = &[
+    Mapping::new(0, Button::Center),
+    Mapping::new(1, Button::Left),
+    Mapping::new(2, Button::Right),
+    Mapping::new(3, Button::Up),
+    Mapping::new(4, Button::Down),
+    Mapping::new(5, Button::A),
+    Mapping::new(6, Button::B),
+];
+
+/// The button codes of the standard layout mounted upside down, with Up and
+/// Down and Left and Right swapped.
+const UPSIDE_DOWN: &[Mapping] = &[
+    Mapping::new(0, Button::Center),
+    Mapping::new(1, Button::Right),
+    Mapping::new(2, Button::Left),
+    Mapping::new(3, Button::Down),
+    Mapping::new(4, Button::Up),
+    Mapping::new(5, Button::A),
+    Mapping::new(6, Button::B),
+];
+
+/// A preset mapping of button codes.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    Standard,
+    UpsideDown,
+}
+
+impl Layout {
+    pub fn mappings(self) -> &'static [Mapping] {
+        match self {
+            Layout::Standard => STANDARD,
+            Layout::UpsideDown => UPSIDE_DOWN,
+        }
+    }
+}
+
+/// A `peach_buttons` button code and the button it stands for.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Mapping {
+    pub code: u8,
+    pub button: Button,
+    /// The name printed on the button, used in on-screen hints.
+    pub label: Option<String>,
+}
+
+impl Mapping {
+    const fn new(code: u8, button: Button) -> Mapping {
+        Mapping {
+            code,
+            button,
+            label: None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Press {
     pub button_code: u8,
@@ -21,12 +89,19 @@ struct ButtonMsg {
 #[derive(Debug)]
 pub struct Client<'a> {
     pub out: Sender,
-    pub s: &'a crossbeam_channel::Sender<u8>,
+    pub s: &'a crossbeam_channel::Sender<Input>,
+    /// Set once the websocket handshake completes.
+    pub opened: &'a Cell<bool>,
 }
 
 impl<'a> Handler for Client<'a> {
     /// Sends request to `peach_buttons` to subscribe to emitted events.
     fn on_open(&mut self, _: Handshake) -> ws::Result<()> {
+        self.opened.set(true);
+        if let Err(err) = self.s.send(Input::ButtonsOnline) {
+            error!("Problem sending online status over channel: {}", err);
+            return self.out.close(CloseCode::Normal);
+        }
         info!("Subscribing to peach_buttons microservice over ws.");
         let subscribe = json!({
             "id":1,
@@ -37,25 +112,34 @@ impl<'a> Handler for Client<'a> {
         self.out.send(data)
     }
 
-    /// Displays JSON-RPC request from `peach_buttons`.
+    /// Extracts the button code from a JSON-RPC request sent by
+    /// `peach_buttons` and passes it to `state_changer`.
+    ///
+    /// Malformed messages are logged and skipped. If the state machine is no
+    /// longer receiving, the connection is closed so that `run` can shut down.
     fn on_message(&mut self, msg: Message) -> ws::Result<()> {
         info!("Received ws message from peach_buttons.");
-        // button_code must be extracted from the request and passed to
-        // state_changer
-        let m: String = msg.into_text()?;
+        let m = match msg.into_text() {
+            Ok(m) => m,
+            Err(err) => {
+                warn!("Skipping non-text ws message: {}", err);
+                return Ok(());
+            }
+        };
         // distinguish button_press events from other received jsonrpc requests
         if m.contains(r"params") {
-            // serialize msg string into a struct
-            let bm: ButtonMsg = serde_json::from_str(&m).unwrap_or_else(|err| {
-                error!("Problem serializing button_code msg: {}", err);
-                process::exit(1);
-            });
+            let button_code = match parse_button_code(&m) {
+                Ok(code) => code,
+                Err(err) => {
+                    warn!("Skipping invalid button message: {}", err);
+                    return Ok(());
+                }
+            };
             debug!("Sending button code to state_changer.");
-            // send the button_code parameter to state_changer
-            self.s.send(bm.params[0]).unwrap_or_else(|err| {
+            if let Err(err) = self.s.send(Input::Button(button_code)) {
                 error!("Problem sending button_code over channel: {}", err);
-                process::exit(1);
-            });
+                return self.out.close(CloseCode::Normal);
+            }
         }
         Ok(())
     }
@@ -80,3 +164,110 @@ impl<'a> Handler for Client<'a> {
         error!("The server encountered an error: {:?}", err);
     }
 }
+
+/// Parses a JSON-RPC button press notification and returns the button code.
+fn parse_button_code(msg: &str) -> Result<u8, MenuError> {
+    let bm: ButtonMsg = serde_json::from_str(msg)?;
+    bm.params
+        .first()
+        .copied()
+        .ok_or(MenuError::MissingButtonCode)
+}
+
+/// Connects to the `peach_buttons` websocket server and blocks until the
+/// connection is closed.
+///
+/// # Arguments
+///
+/// * `addr` - The websocket URL of the `peach_buttons` server.
+/// * `s` - A `crossbeam_channel::Sender` for passing input to the state machine.
+/// * `opened` - Set to `true` if the websocket handshake completes.
+///
+pub fn connect(
+    addr: &str,
+    s: &crossbeam_channel::Sender<Input>,
+    opened: &Cell<bool>,
+) -> Result<(), MenuError> {
+    ws::connect(addr, |out| Client { out, s, opened })?;
+
+    Ok(())
+}
+
+/// Exponential backoff for reconnecting to `peach_buttons`.
+///
+/// The delay doubles after each failed attempt, up to `BACKOFF_MAX`, and is
+/// reset once a connection has been successfully opened.
+#[derive(Debug)]
+pub struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    pub fn new() -> Backoff {
+        Backoff {
+            delay: BACKOFF_INITIAL,
+        }
+    }
+
+    /// Returns the delay to wait before the next attempt and doubles the
+    /// delay for the attempt after that.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = std::cmp::min(self.delay * 2, BACKOFF_MAX);
+        delay
+    }
+
+    /// Resets the delay to its initial value.
+    pub fn reset(&mut self) {
+        self.delay = BACKOFF_INITIAL;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<u64> = (0..8).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 32, 32]);
+    }
+
+    #[test]
+    fn backoff_resets_to_the_initial_delay() {
+        let mut backoff = Backoff::new();
+        backoff.next_delay();
+        backoff.next_delay();
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), BACKOFF_INITIAL);
+    }
+
+    #[test]
+    fn button_code_is_the_first_param() {
+        let msg = r#"{"jsonrpc":"2.0","method":"button_press","params":[5]}"#;
+        assert_eq!(parse_button_code(msg).unwrap(), 5);
+    }
+
+    #[test]
+    fn empty_params_are_an_error() {
+        let msg = r#"{"jsonrpc":"2.0","method":"button_press","params":[]}"#;
+        assert!(matches!(
+            parse_button_code(msg),
+            Err(MenuError::MissingButtonCode)
+        ));
+    }
+
+    #[test]
+    fn malformed_messages_are_an_error() {
+        let msg = r#"{"jsonrpc":"2.0","method":"button_press","params":["five"]}"#;
+        assert!(parse_button_code(msg).is_err());
+        assert!(parse_button_code("params").is_err());
+    }
+}
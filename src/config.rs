@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+use std::{env, fs, io};
+
+use log::info;
+use serde::Deserialize;
+
+use crate::buttons::{Layout, Mapping};
+use crate::error::MenuError;
+use crate::menu::{Command, Menu, Target, NETWORK_CONF_MENU};
+use crate::network::{Interface, Role};
+use crate::state_machine::Button;
+
+/// The default configuration, including the built-in menu layout.
+const DEFAULT_CONFIG: &str = include_str!("../config/peach-menu.toml");
+
+/// The configuration file path used when `PEACH_MENU_CONFIG` is not set.
+const DEFAULT_CONFIG_PATH: &str = "/etc/peach-menu/config.toml";
+
+/// Configuration for `peach-menu`.
+///
+/// Any value missing from the configuration file is taken from the default
+/// configuration. Menus declared in the file replace the default menus with
+/// the same identifier and the other default menus are kept.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    /// The menu shown when leaving the logo screen.
+    #[serde(default = "default_root")]
+    pub root: String,
+    /// The menus, keyed by identifier.
+    #[serde(default)]
+    pub menus: HashMap<String, Menu>,
+    #[serde(default)]
+    pub refresh: Refresh,
+    #[serde(default)]
+    pub confirm: Confirmation,
+    #[serde(default)]
+    pub ap: Ap,
+    #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
+    pub network: Network,
+    #[serde(default)]
+    pub idle: Idle,
+    #[serde(default)]
+    pub burn_in: BurnIn,
+    #[serde(default)]
+    pub gestures: Gestures,
+    #[serde(default)]
+    pub navigation: Navigation,
+    #[serde(default)]
+    pub buttons: Buttons,
+}
+
+/// How often screens showing live data are redrawn, in seconds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Refresh {
+    pub network: u64,
+    pub stats: u64,
+}
+
+/// Settings for the confirmation dialog shown before destructive actions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Confirmation {
+    /// Seconds after which the dialog is dismissed without running the action.
+    pub timeout: u8,
+}
+
+impl Default for Confirmation {
+    fn default() -> Self {
+        Confirmation { timeout: 10 }
+    }
+}
+
+/// The access point shown when the `hostapd` configuration cannot be read.
+/// Settings which are not set are shown as unknown.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Ap {
+    pub ssid: Option<String>,
+    pub passphrase: Option<String>,
+}
+
+/// Settings for the storage screen.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Storage {
+    /// Filesystems at or above this percentage of use are highlighted.
+    pub threshold: u32,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage { threshold: 90 }
+    }
+}
+
+/// Settings for the network screen.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Network {
+    /// The interfaces shown, in order.
+    pub interfaces: Vec<Interface>,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        let interface = |name: &str, role| Interface {
+            name: name.to_string(),
+            role,
+        };
+        Network {
+            interfaces: vec![
+                interface("eth0", Role::Ethernet),
+                interface("wlan0", Role::Client),
+                interface("ap0", Role::Ap),
+                interface("usb0", Role::Usb),
+            ],
+        }
+    }
+}
+
+/// Seconds without a button press before the display shows a clock
+/// screensaver and before it is powered off. Zero disables either.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Idle {
+    pub screensaver: u64,
+    pub off: u64,
+}
+
+impl Default for Idle {
+    fn default() -> Self {
+        Idle {
+            screensaver: 120,
+            off: 600,
+        }
+    }
+}
+
+/// Settings for burn-in protection.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BurnIn {
+    /// The largest offset, in pixels, by which the screen is moved right and
+    /// down.
+    pub shift: u32,
+    /// Seconds between moves of the screen. Zero disables moving.
+    pub shift_interval: u64,
+    /// Seconds between inversions of the screen. Zero disables inversion.
+    pub invert_interval: u64,
+}
+
+impl Default for BurnIn {
+    fn default() -> Self {
+        BurnIn {
+            shift: 1,
+            shift_interval: 60,
+            invert_interval: 0,
+        }
+    }
+}
+
+/// Timing of button gestures, in milliseconds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Gestures {
+    /// The longest time between two presses of a button for a double press.
+    /// Zero disables double presses.
+    pub double: u64,
+    /// The longest time between presses of two buttons for a chord. Zero
+    /// disables chords.
+    pub chord: u64,
+}
+
+impl Default for Gestures {
+    fn default() -> Self {
+        Gestures {
+            double: 400,
+            chord: 100,
+        }
+    }
+}
+
+/// What the Center, Left and Right buttons do outside of the keyboard.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Navigation {
+    pub center: Command,
+    pub left: Command,
+    pub right: Command,
+}
+
+impl Default for Navigation {
+    fn default() -> Self {
+        Navigation {
+            center: Command::Select,
+            left: Command::Back,
+            right: Command::Forward,
+        }
+    }
+}
+
+/// The buttons which the `peach-buttons` button codes stand for.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Buttons {
+    /// The preset used when `map` is empty.
+    pub layout: Layout,
+    pub map: Vec<Mapping>,
+    /// The names printed on the buttons, overriding those in the mapping.
+    pub labels: Labels,
+}
+
+impl Default for Buttons {
+    fn default() -> Self {
+        Buttons {
+            layout: Layout::Standard,
+            map: Vec::new(),
+            labels: Labels::default(),
+        }
+    }
+}
+
+/// The names printed on the buttons, for those which have one.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Labels {
+    pub center: Option<String>,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub down: Option<String>,
+    pub up: Option<String>,
+    pub a: Option<String>,
+    pub b: Option<String>,
+}
+
+impl Labels {
+    /// Returns the name printed on `button`, if set.
+    pub fn get(&self, button: Button) -> Option<&String> {
+        match button {
+            Button::Center => self.center.as_ref(),
+            Button::Left => self.left.as_ref(),
+            Button::Right => self.right.as_ref(),
+            Button::Down => self.down.as_ref(),
+            Button::Up => self.up.as_ref(),
+            Button::A => self.a.as_ref(),
+            Button::B => self.b.as_ref(),
+        }
+    }
+}
+
+impl Buttons {
+    /// Returns the configured mapping, or that of the layout if none is.
+    pub fn mappings(&self) -> &[Mapping] {
+        if self.map.is_empty() {
+            self.layout.mappings()
+        } else {
+            &self.map
+        }
+    }
+
+    /// Returns the button which `code` stands for.
+    pub fn decode(&self, code: u8) -> Option<Button> {
+        self.mappings()
+            .iter()
+            .find(|mapping| mapping.code == code)
+            .map(|mapping| mapping.button)
+    }
+
+    /// Returns the code which stands for `button`.
+    pub fn encode(&self, button: Button) -> Option<u8> {
+        self.mappings()
+            .iter()
+            .find(|mapping| mapping.button == button)
+            .map(|mapping| mapping.code)
+    }
+
+    /// Returns the name shown in on-screen hints for `button`.
+    pub fn label(&self, button: Button) -> String {
+        if let Some(label) = self.labels.get(button) {
+            return label.clone();
+        }
+        self.mappings()
+            .iter()
+            .find(|mapping| mapping.button == button)
+            .and_then(|mapping| mapping.label.clone())
+            .unwrap_or_else(|| button.name().to_string())
+    }
+}
+
+impl Default for Refresh {
+    fn default() -> Self {
+        Refresh {
+            network: 5,
+            stats: 2,
+        }
+    }
+}
+
+fn default_root() -> String {
+    Config::default().root
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        toml::from_str(DEFAULT_CONFIG).expect("default configuration is valid")
+    }
+}
+
+impl Config {
+    /// Loads the configuration from the file named by the `PEACH_MENU_CONFIG`
+    /// environment variable, or from `/etc/peach-menu/config.toml`.
+    ///
+    /// The default configuration is returned if the file does not exist.
+    pub fn load() -> Result<Config, MenuError> {
+        let path =
+            env::var("PEACH_MENU_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                info!("No configuration file at {}. Using defaults.", path);
+                return Ok(Config::default());
+            }
+            Err(err) => return Err(MenuError::Io(err)),
+        };
+        info!("Loading configuration from {}.", path);
+        let mut config: Config = toml::from_str(&contents)?;
+        let mut menus = Config::default().menus;
+        menus.extend(config.menus);
+        config.menus = menus;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks that the root and network configuration menus exist, that
+    /// every menu has at least one item, that every menu target refers to
+    /// a declared menu, that at least one network interface is listed and
+    /// that every button is mapped to exactly one code.
+    pub fn validate(&self) -> Result<(), MenuError> {
+        if self.network.interfaces.is_empty() {
+            return Err(MenuError::Config(
+                "no network interfaces are listed".to_string(),
+            ));
+        }
+        let codes = self.buttons.mappings().iter().map(|mapping| mapping.code);
+        for (i, code) in codes.clone().enumerate() {
+            if codes.clone().skip(i + 1).any(|other| other == code) {
+                return Err(MenuError::Config(format!(
+                    "button code {} is mapped more than once",
+                    code
+                )));
+            }
+        }
+        for button in &Button::ALL {
+            let mapped = self
+                .buttons
+                .mappings()
+                .iter()
+                .filter(|m| m.button == *button);
+            if mapped.count() != 1 {
+                return Err(MenuError::Config(format!(
+                    "button {} must be mapped to exactly one code",
+                    button.name()
+                )));
+            }
+        }
+        for id in &[self.root.as_str(), NETWORK_CONF_MENU] {
+            if !self.menus.contains_key(*id) {
+                return Err(MenuError::Config(format!("menu '{}' is not declared", id)));
+            }
+        }
+        for (id, menu) in &self.menus {
+            if menu.items.is_empty() {
+                return Err(MenuError::Config(format!("menu '{}' has no items", id)));
+            }
+            let targets = menu
+                .items
+                .iter()
+                .map(|item| &item.target)
+                .chain(menu.back.iter());
+            for target in targets {
+                if let Target::Menu(ref next) = *target {
+                    if !self.menus.contains_key(next) {
+                        return Err(MenuError::Config(format!(
+                            "menu '{}' refers to undeclared menu '{}'",
+                            id, next
+                        )));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(code: u8, button: Button) -> Mapping {
+        Mapping {
+            code,
+            button,
+            label: None,
+        }
+    }
+
+    fn standard() -> Vec<Mapping> {
+        Layout::Standard.mappings().to_vec()
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn layouts_decode_codes() {
+        let mut buttons = Buttons::default();
+        assert_eq!(buttons.decode(1), Some(Button::Left));
+        assert_eq!(buttons.decode(5), Some(Button::A));
+        assert_eq!(buttons.decode(7), None);
+        buttons.layout = Layout::UpsideDown;
+        assert_eq!(buttons.decode(1), Some(Button::Right));
+        assert_eq!(buttons.decode(3), Some(Button::Down));
+        assert_eq!(buttons.decode(0), Some(Button::Center));
+    }
+
+    #[test]
+    fn map_replaces_layout() {
+        let mut buttons = Buttons {
+            map: standard(),
+            ..Buttons::default()
+        };
+        buttons.map[5].code = 10;
+        buttons.map[6].code = 11;
+        assert_eq!(buttons.decode(5), None);
+        assert_eq!(buttons.decode(10), Some(Button::A));
+        assert_eq!(buttons.decode(11), Some(Button::B));
+    }
+
+    #[test]
+    fn labels_override_mapping() {
+        let mut buttons = Buttons::default();
+        assert_eq!(buttons.label(Button::A), "A");
+        buttons.map = standard();
+        buttons.map[5].label = Some("K1".to_string());
+        assert_eq!(buttons.label(Button::A), "K1");
+        buttons.labels.a = Some("Key 1".to_string());
+        assert_eq!(buttons.label(Button::A), "Key 1");
+        assert_eq!(buttons.label(Button::B), "B");
+    }
+
+    #[test]
+    fn encode_is_the_inverse_of_decode() {
+        let config: Config = toml::from_str("[buttons]\nlayout = \"upside_down\"\n").unwrap();
+        for &button in &Button::ALL {
+            let code = config.buttons.encode(button).unwrap();
+            assert_eq!(config.buttons.decode(code), Some(button));
+        }
+    }
+
+    #[test]
+    fn labels_are_read_for_a_layout() {
+        let config: Config =
+            toml::from_str("[buttons]\nlayout = \"upside_down\"\nlabels = { b = \"K2\" }\n")
+                .unwrap();
+        assert_eq!(config.buttons.layout, Layout::UpsideDown);
+        assert_eq!(config.buttons.label(Button::B), "K2");
+    }
+
+    #[test]
+    fn every_button_must_be_mapped() {
+        let mut config = Config::default();
+        config.buttons.map = vec![mapping(5, Button::A), mapping(6, Button::B)];
+        assert!(config.validate().is_err());
+        config.buttons.map = standard();
+        config.buttons.map.pop();
+        assert!(config.validate().is_err());
+        config.buttons.map.push(mapping(6, Button::B));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn button_mapped_twice_is_rejected() {
+        let mut config = Config::default();
+        config.buttons.map = standard();
+        config.buttons.map.push(mapping(7, Button::A));
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn code_mapped_twice_is_rejected() {
+        let mut config = Config::default();
+        config.buttons.map = standard();
+        config.buttons.map[6].code = 5;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn menus_are_checked() {
+        let config = Config {
+            root: "missing".to_string(),
+            ..Config::default()
+        };
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.menus.get_mut("home").unwrap().back = Some(Target::Menu("missing".to_string()));
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config
+            .menus
+            .get_mut(NETWORK_CONF_MENU)
+            .unwrap()
+            .items
+            .clear();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn interfaces_are_required() {
+        let mut config = Config::default();
+        config.network.interfaces.clear();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,463 @@
+use std::io::Write;
+
+use peach_lib::oled_client;
+
+use crate::error::MenuError;
+use crate::font;
+
+/// The width of the screen in pixels.
+pub const WIDTH: u32 = 128;
+/// The height of the screen in pixels.
+pub const HEIGHT: u32 = 64;
+
+/// An output device for rendering menu states.
+///
+/// The operations mirror those offered by `peach-oled`: changes are made to a
+/// buffer with `clear`, `write` and `draw`, and shown on the screen with
+/// `flush`. States draw their whole screen after `clear`, so that what is
+/// shown never depends on what was shown before.
+pub trait Display {
+    /// Clears the buffer.
+    fn clear(&mut self) -> Result<(), MenuError>;
+
+    /// Writes `text` to the buffer with its top-left corner at (`x`, `y`).
+    ///
+    /// `font` is one of the `peach-oled` font sizes: `6x8`, `6x12`, `8x16` or
+    /// `12x16`.
+    fn write(&mut self, x: i32, y: i32, text: &str, font: &str) -> Result<(), MenuError>;
+
+    /// Draws a bitmap to the buffer with its top-left corner at (`x`, `y`).
+    ///
+    /// `bytes` holds one bit per pixel, row by row, with the most significant
+    /// bit leftmost. Each row is padded to a whole number of bytes.
+    fn draw(
+        &mut self,
+        bytes: &[u8],
+        width: u32,
+        height: u32,
+        x: i32,
+        y: i32,
+    ) -> Result<(), MenuError>;
+
+    /// Shows the buffer on the screen.
+    fn flush(&mut self) -> Result<(), MenuError>;
+
+    /// Turns the screen on or off.
+    fn power(&mut self, on: bool) -> Result<(), MenuError>;
+
+    /// Called every second while the screen is on, for displays which change
+    /// what is shown over time.
+    fn tick(&mut self) -> Result<(), MenuError> {
+        Ok(())
+    }
+}
+
+/// Renders to the OLED screen through the `peach-oled` JSON-RPC service.
+#[derive(Debug, Default)]
+pub struct Oled;
+
+impl Display for Oled {
+    fn clear(&mut self) -> Result<(), MenuError> {
+        oled_client::clear()?;
+        Ok(())
+    }
+
+    fn write(&mut self, x: i32, y: i32, text: &str, font: &str) -> Result<(), MenuError> {
+        oled_client::write(x, y, text, font)?;
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        bytes: &[u8],
+        width: u32,
+        height: u32,
+        x: i32,
+        y: i32,
+    ) -> Result<(), MenuError> {
+        oled_client::draw(bytes.to_vec(), width, height, x, y)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), MenuError> {
+        oled_client::flush()?;
+        Ok(())
+    }
+
+    fn power(&mut self, on: bool) -> Result<(), MenuError> {
+        oled_client::power(on)?;
+        Ok(())
+    }
+}
+
+/// A 128x64 monochrome in-memory framebuffer.
+///
+/// Text is rasterized with the 6x8 font in `font`; larger font sizes are
+/// produced by scaling the 6x8 glyphs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    pixels: Vec<bool>,
+    on: bool,
+}
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer {
+            pixels: vec![false; (WIDTH * HEIGHT) as usize],
+            on: true,
+        }
+    }
+
+    /// Returns whether the pixel at (`x`, `y`) is set. Pixels outside the
+    /// screen are never set.
+    pub fn get(&self, x: i32, y: i32) -> bool {
+        match Framebuffer::index(x, y) {
+            Some(i) => self.pixels[i],
+            None => false,
+        }
+    }
+
+    /// Sets or unsets the pixel at (`x`, `y`). Pixels outside the screen are
+    /// ignored.
+    pub fn set(&mut self, x: i32, y: i32, value: bool) {
+        if let Some(i) = Framebuffer::index(x, y) {
+            self.pixels[i] = value;
+        }
+    }
+
+    /// Returns whether the screen is powered on.
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Returns the buffer as a bitmap in the format accepted by
+    /// `Display::draw`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let stride = (WIDTH / 8) as usize;
+        let mut bytes = vec![0; stride * HEIGHT as usize];
+        for (i, _) in self.pixels.iter().enumerate().filter(|(_, p)| **p) {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+        bytes
+    }
+
+    /// Returns the buffer as ASCII art, packing two rows of pixels into each
+    /// line of text and surrounded by a border.
+    pub fn to_ascii(&self) -> String {
+        let border = format!("+{}+\n", "-".repeat(WIDTH as usize));
+        let mut art = border.clone();
+        for y in (0..HEIGHT as i32).step_by(2) {
+            art.push('|');
+            for x in 0..WIDTH as i32 {
+                let c = match (self.get(x, y), self.get(x, y + 1)) {
+                    (true, true) => ':',
+                    (true, false) => '\'',
+                    (false, true) => '.',
+                    (false, false) => ' ',
+                };
+                art.push(c);
+            }
+            art.push_str("|\n");
+        }
+        art.push_str(&border);
+        art
+    }
+
+    fn index(x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= WIDTH as i32 || y >= HEIGHT as i32 {
+            return None;
+        }
+        Some(y as usize * WIDTH as usize + x as usize)
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses a `peach-oled` font size such as `6x8` into a width and height,
+/// falling back to 6x8.
+fn font_size(font: &str) -> (u32, u32) {
+    let mut dims = font.split('x').map(str::parse::<u32>);
+    match (dims.next(), dims.next()) {
+        (Some(Ok(w)), Some(Ok(h))) if w > 0 && h > 0 => (w, h),
+        _ => (font::WIDTH, font::HEIGHT),
+    }
+}
+
+impl Display for Framebuffer {
+    fn clear(&mut self) -> Result<(), MenuError> {
+        for p in self.pixels.iter_mut() {
+            *p = false;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, x: i32, y: i32, text: &str, font: &str) -> Result<(), MenuError> {
+        let (w, h) = font_size(font);
+        for (n, c) in text.chars().enumerate() {
+            let left = x + (n as u32 * w) as i32;
+            for cy in 0..h {
+                for cx in 0..w {
+                    let on = font::pixel(c, cx * font::WIDTH / w, cy * font::HEIGHT / h);
+                    self.set(left + cx as i32, y + cy as i32, on);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        bytes: &[u8],
+        width: u32,
+        height: u32,
+        x: i32,
+        y: i32,
+    ) -> Result<(), MenuError> {
+        let stride = ((width + 7) / 8) as usize;
+        for by in 0..height {
+            for bx in 0..width {
+                let byte = bytes
+                    .get(by as usize * stride + bx as usize / 8)
+                    .unwrap_or(&0);
+                let on = byte & (0x80 >> (bx % 8)) != 0;
+                self.set(x + bx as i32, y + by as i32, on);
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), MenuError> {
+        Ok(())
+    }
+
+    fn power(&mut self, on: bool) -> Result<(), MenuError> {
+        self.on = on;
+        Ok(())
+    }
+}
+
+/// Renders the screen as ASCII art to a terminal or other writer.
+///
+/// The screen is redrawn in place on every `flush`. Lines are terminated
+/// with `\r\n` so that output is also correct in raw mode.
+#[derive(Debug)]
+pub struct Terminal<W: Write> {
+    framebuffer: Framebuffer,
+    out: W,
+    caption: String,
+}
+
+impl<W: Write> Terminal<W> {
+    pub fn new(out: W) -> Terminal<W> {
+        Terminal {
+            framebuffer: Framebuffer::new(),
+            out,
+            caption: String::new(),
+        }
+    }
+
+    /// Sets text to be printed below the screen on every redraw.
+    pub fn with_caption(mut self, caption: &str) -> Terminal<W> {
+        self.caption = caption.to_string();
+        self
+    }
+
+    /// Returns the framebuffer holding the current contents of the screen.
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    fn print(&mut self) -> Result<(), MenuError> {
+        let art = if self.framebuffer.is_on() {
+            self.framebuffer.to_ascii()
+        } else {
+            Framebuffer::new().to_ascii()
+        };
+        let text = format!("{}{}", art, self.caption).replace('\n', "\r\n");
+        // move the cursor home and clear the terminal before redrawing
+        write!(self.out, "\x1b[H\x1b[2J{}", text)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Display for Terminal<W> {
+    fn clear(&mut self) -> Result<(), MenuError> {
+        self.framebuffer.clear()
+    }
+
+    fn write(&mut self, x: i32, y: i32, text: &str, font: &str) -> Result<(), MenuError> {
+        self.framebuffer.write(x, y, text, font)
+    }
+
+    fn draw(
+        &mut self,
+        bytes: &[u8],
+        width: u32,
+        height: u32,
+        x: i32,
+        y: i32,
+    ) -> Result<(), MenuError> {
+        self.framebuffer.draw(bytes, width, height, x, y)
+    }
+
+    fn flush(&mut self) -> Result<(), MenuError> {
+        self.print()
+    }
+
+    fn power(&mut self, on: bool) -> Result<(), MenuError> {
+        self.framebuffer.power(on)?;
+        self.print()
+    }
+}
+
+/// A display which records the calls made to it, for tests. Clones share
+/// the record, so it can be read after a clone is handed over.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct Recording(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+#[cfg(test)]
+impl Recording {
+    /// Returns the calls made so far, e.g. `write Hello` or
+    /// `draw 8x8 at 0,0`.
+    pub fn calls(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn record(&self, call: String) -> Result<(), MenuError> {
+        self.0.lock().unwrap().push(call);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Display for Recording {
+    fn clear(&mut self) -> Result<(), MenuError> {
+        self.record("clear".to_string())
+    }
+
+    fn write(&mut self, _x: i32, _y: i32, text: &str, _font: &str) -> Result<(), MenuError> {
+        self.record(format!("write {}", text))
+    }
+
+    fn draw(&mut self, _: &[u8], width: u32, height: u32, x: i32, y: i32) -> Result<(), MenuError> {
+        self.record(format!("draw {}x{} at {},{}", width, height, x, y))
+    }
+
+    fn flush(&mut self) -> Result<(), MenuError> {
+        self.record("flush".to_string())
+    }
+
+    fn power(&mut self, on: bool) -> Result<(), MenuError> {
+        self.record(format!("power {}", on))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_outside_the_screen_are_ignored() {
+        let mut frame = Framebuffer::new();
+        frame.set(-1, 0, true);
+        frame.set(0, HEIGHT as i32, true);
+        assert_eq!(frame, Framebuffer::new());
+        assert!(!frame.get(WIDTH as i32, 0));
+    }
+
+    #[test]
+    fn text_is_drawn_with_the_font() {
+        let mut frame = Framebuffer::new();
+        frame.write(6, 0, "!", "6x8").unwrap();
+        assert!(frame.get(8, 0));
+        assert!(!frame.get(8, 5));
+        assert!(frame.get(8, 6));
+        frame.clear().unwrap();
+        assert_eq!(frame, Framebuffer::new());
+    }
+
+    #[test]
+    fn larger_fonts_scale_the_glyphs() {
+        let mut frame = Framebuffer::new();
+        frame.write(0, 0, "!", "12x16").unwrap();
+        for &(x, y) in &[(4, 0), (5, 1), (4, 12), (5, 13)] {
+            assert!(frame.get(x, y));
+        }
+        assert!(!frame.get(4, 10));
+        assert!(!frame.get(3, 0));
+    }
+
+    #[test]
+    fn unknown_font_sizes_fall_back_to_6x8() {
+        assert_eq!(font_size("12x16"), (12, 16));
+        assert_eq!(font_size("large"), (6, 8));
+        assert_eq!(font_size("0x8"), (6, 8));
+    }
+
+    #[test]
+    fn bytes_are_packed_most_significant_bit_first() {
+        let mut frame = Framebuffer::new();
+        frame.set(0, 0, true);
+        frame.set(9, 1, true);
+        let bytes = frame.to_bytes();
+        assert_eq!(bytes.len(), (WIDTH * HEIGHT / 8) as usize);
+        assert_eq!(bytes[0], 0x80);
+        assert_eq!(bytes[16 + 1], 0x40);
+        assert_eq!(bytes.iter().filter(|&&byte| byte != 0).count(), 2);
+
+        let mut drawn = Framebuffer::new();
+        drawn.draw(&bytes, WIDTH, HEIGHT, 0, 0).unwrap();
+        assert_eq!(drawn, frame);
+    }
+
+    #[test]
+    fn bitmaps_are_drawn_at_an_offset() {
+        let mut frame = Framebuffer::new();
+        // a 10 pixel wide row takes two bytes
+        frame.draw(&[0x80, 0x40], 10, 1, 3, 2).unwrap();
+        assert!(frame.get(3, 2));
+        assert!(frame.get(12, 2));
+        assert!(!frame.get(4, 2));
+    }
+
+    #[test]
+    fn ascii_art_packs_two_rows_per_line() {
+        let mut frame = Framebuffer::new();
+        frame.set(0, 0, true);
+        frame.set(1, 1, true);
+        frame.set(2, 0, true);
+        frame.set(2, 1, true);
+        let art = frame.to_ascii();
+        let lines: Vec<&str> = art.lines().collect();
+        assert_eq!(lines.len(), HEIGHT as usize / 2 + 2);
+        assert_eq!(lines[0], format!("+{}+", "-".repeat(WIDTH as usize)));
+        assert!(lines[1].starts_with("|'.: "));
+        assert!(lines[1].ends_with(" |"));
+        assert_eq!(lines[1].len(), WIDTH as usize + 2);
+    }
+
+    #[test]
+    fn terminal_redraws_on_flush_and_power() {
+        let mut terminal = Terminal::new(Vec::new()).with_caption("keys");
+        terminal.write(0, 0, "!", "6x8").unwrap();
+        assert!(terminal.out.is_empty());
+        terminal.flush().unwrap();
+        let out = String::from_utf8(terminal.out.clone()).unwrap();
+        assert!(out.starts_with("\x1b[H\x1b[2J+-"));
+        assert!(out.contains("|  :   "));
+        assert!(out.ends_with("+\r\nkeys"));
+        assert!(!out.contains("|\n"));
+
+        terminal.out.clear();
+        terminal.power(false).unwrap();
+        let out = String::from_utf8(terminal.out.clone()).unwrap();
+        assert!(!out.contains(':'));
+        assert!(terminal.framebuffer().get(2, 0));
+    }
+}
@@ -0,0 +1,108 @@
+use std::{error, fmt, io};
+
+use peach_lib::error::PeachError;
+use qrcode::types::QrError;
+
+/// The errors which may occur while running the menu.
+#[derive(Debug)]
+pub enum MenuError {
+    /// A message could not be passed between threads.
+    Channel(String),
+    /// The configuration is invalid.
+    Config(String),
+    /// An I/O operation failed.
+    Io(io::Error),
+    /// A `peach_buttons` notification did not contain a button code.
+    MissingButtonCode,
+    /// A `peach_buttons` notification or microservice response could not be
+    /// parsed.
+    Parse(serde_json::Error),
+    /// A JSON-RPC call to a PeachCloud microservice failed.
+    Peach(PeachError),
+    /// The data for a QR code could not be encoded.
+    Qr(QrError),
+    /// The configuration file could not be parsed.
+    Toml(toml::de::Error),
+    /// The websocket connection to `peach_buttons` failed.
+    Ws(Box<ws::Error>),
+}
+
+impl fmt::Display for MenuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MenuError::Channel(ref msg) => write!(f, "Channel error: {}", msg),
+            MenuError::Config(ref msg) => write!(f, "Invalid configuration: {}", msg),
+            MenuError::Io(ref err) => write!(f, "I/O error: {}", err),
+            MenuError::MissingButtonCode => {
+                write!(f, "Button message did not contain a button code")
+            }
+            MenuError::Parse(ref err) => write!(f, "Failed to parse message: {}", err),
+            MenuError::Peach(ref err) => write!(f, "Microservice error: {}", err),
+            MenuError::Qr(ref err) => write!(f, "Failed to encode QR code: {}", err),
+            MenuError::Toml(ref err) => write!(f, "Failed to parse configuration: {}", err),
+            MenuError::Ws(ref err) => write!(f, "Websocket error: {}", err),
+        }
+    }
+}
+
+impl error::Error for MenuError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            MenuError::Io(ref err) => Some(err),
+            MenuError::Parse(ref err) => Some(err),
+            MenuError::Peach(ref err) => Some(err),
+            MenuError::Qr(ref err) => Some(err),
+            MenuError::Toml(ref err) => Some(err),
+            MenuError::Ws(ref err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl<T> From<crossbeam_channel::SendError<T>> for MenuError {
+    fn from(err: crossbeam_channel::SendError<T>) -> MenuError {
+        MenuError::Channel(err.to_string())
+    }
+}
+
+impl From<crossbeam_channel::RecvError> for MenuError {
+    fn from(err: crossbeam_channel::RecvError) -> MenuError {
+        MenuError::Channel(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for MenuError {
+    fn from(err: serde_json::Error) -> MenuError {
+        MenuError::Parse(err)
+    }
+}
+
+impl From<io::Error> for MenuError {
+    fn from(err: io::Error) -> MenuError {
+        MenuError::Io(err)
+    }
+}
+
+impl From<QrError> for MenuError {
+    fn from(err: QrError) -> MenuError {
+        MenuError::Qr(err)
+    }
+}
+
+impl From<PeachError> for MenuError {
+    fn from(err: PeachError) -> MenuError {
+        MenuError::Peach(err)
+    }
+}
+
+impl From<toml::de::Error> for MenuError {
+    fn from(err: toml::de::Error) -> MenuError {
+        MenuError::Toml(err)
+    }
+}
+
+impl From<ws::Error> for MenuError {
+    fn from(err: ws::Error) -> MenuError {
+        MenuError::Ws(Box::new(err))
+    }
+}
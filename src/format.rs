@@ -0,0 +1,129 @@
+//! Formatting of values for the display.
+//!
+//! Text is drawn in the 6x8 font, so a full 128 pixel line holds 21
+//! characters. Screens pass every value through these helpers so that
+//! units are consistent and lines never run off the display.
+
+use crate::display;
+use crate::font;
+
+/// The number of characters which fit on a line.
+pub const COLUMNS: usize = (display::WIDTH / font::WIDTH) as usize;
+
+/// Returns `secs` as days, hours and minutes, e.g. `1d 12h 33m`. Leading
+/// units which are zero are left out.
+pub fn duration(secs: u64) -> String {
+    let mins = secs / 60;
+    let (days, hours, mins) = (mins / 1440, mins / 60 % 24, mins % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, mins)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, mins)
+    } else {
+        format!("{}m", mins)
+    }
+}
+
+/// Returns `bytes` in the largest unit which keeps the value at or above
+/// one, with one decimal place, e.g. `12.3 MB`.
+pub fn bytes(bytes: u64) -> String {
+    let units = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < units.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, units[unit])
+}
+
+/// Returns a throughput in bytes per second, e.g. `1.2 MB/s`.
+pub fn rate(bytes_per_sec: u64) -> String {
+    format!("{}/s", bytes(bytes_per_sec))
+}
+
+/// Returns `value` rounded to a whole percentage, e.g. `42%`.
+pub fn percent(value: f32) -> String {
+    format!("{:.0}%", value)
+}
+
+/// Returns a signal strength, e.g. `-52 dBm`.
+pub fn dbm(dbm: i32) -> String {
+    format!("{} dBm", dbm)
+}
+
+/// Returns `text` shortened to at most `columns` characters. Shortened text
+/// ends with `~` to show that it has been cut.
+pub fn fit(text: &str, columns: usize) -> String {
+    if text.chars().count() <= columns {
+        return text.to_string();
+    }
+    match columns {
+        0 => String::new(),
+        _ => text.chars().take(columns - 1).chain("~".chars()).collect(),
+    }
+}
+
+/// Returns the number of characters which fit between `x` and the right
+/// edge of the display.
+pub fn columns_from(x: i32) -> usize {
+    (display::WIDTH as i32 - x).max(0) as usize / font::WIDTH as usize
+}
+
+/// Returns the x coordinate which right-aligns `text`.
+pub fn right(text: &str) -> i32 {
+    display::WIDTH as i32 - (font::WIDTH as usize * text.chars().count()) as i32
+}
+
+/// Returns the x coordinate which horizontally centers `text`.
+pub fn center(text: &str) -> i32 {
+    right(text).max(0) / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_leaves_out_leading_zero_units() {
+        assert_eq!(duration(59), "0m");
+        assert_eq!(duration(3 * 60), "3m");
+        assert_eq!(duration(2 * 3600 + 5 * 60), "2h 5m");
+        assert_eq!(duration(86400 + 12 * 3600 + 33 * 60), "1d 12h 33m");
+        assert_eq!(duration(86400), "1d 0h 0m");
+    }
+
+    #[test]
+    fn bytes_uses_the_largest_unit() {
+        assert_eq!(bytes(0), "0 B");
+        assert_eq!(bytes(1023), "1023 B");
+        assert_eq!(bytes(1024), "1.0 KB");
+        assert_eq!(bytes(1536 * 1024), "1.5 MB");
+        assert_eq!(bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+        assert_eq!(bytes(2048 * 1024 * 1024 * 1024 * 1024), "2048.0 TB");
+        assert_eq!(rate(2048), "2.0 KB/s");
+    }
+
+    #[test]
+    fn fit_marks_shortened_text() {
+        assert_eq!(fit("PeachCloud", 10), "PeachCloud");
+        assert_eq!(fit("PeachCloud", 6), "Peach~");
+        assert_eq!(fit("PeachCloud", 1), "~");
+        assert_eq!(fit("PeachCloud", 0), "");
+        assert_eq!(fit("čaj čaj", 4), "čaj~");
+    }
+
+    #[test]
+    fn positions_fit_the_display() {
+        assert_eq!(COLUMNS, 21);
+        assert_eq!(columns_from(0), 21);
+        assert_eq!(columns_from(100), 4);
+        assert_eq!(columns_from(200), 0);
+        assert_eq!(right("abc"), 110);
+        assert_eq!(center("abc"), 55);
+        assert_eq!(center(&"x".repeat(30)), 0);
+    }
+}
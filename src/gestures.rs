@@ -0,0 +1,271 @@
+//! Gestures recognised from button presses.
+//!
+//! `peach-buttons` reports each press of a button. Presses are passed on as
+//! events straight away, except for a press of a button used in a chord,
+//! which is held back for the chord time in case the other button follows.
+//! A second press of the same button within the double press time is passed
+//! on as a double press instead of a press.
+//!
+//! Long presses are not recognised: `peach-buttons` does not report when a
+//! button is released, so how long it was held cannot be told.
+
+use std::time::{Duration, Instant};
+
+use crate::config::Gestures;
+use crate::state_machine::{Button, Event};
+
+/// Turns button presses into events.
+#[derive(Debug)]
+pub struct Recognizer {
+    config: Gestures,
+    /// The pairs of buttons which form a chord.
+    chords: Vec<(Button, Button)>,
+    /// A press held back while waiting for the other button of a chord.
+    pending: Option<(Button, Instant)>,
+    /// The last press passed on, for recognising a double press.
+    last: Option<(Button, Instant)>,
+}
+
+impl Recognizer {
+    pub fn new(config: Gestures, chords: &[(Button, Button)]) -> Recognizer {
+        Recognizer {
+            config,
+            chords: chords.to_vec(),
+            pending: None,
+            last: None,
+        }
+    }
+
+    /// Returns the events for a press of `button` at `now`.
+    pub fn press(&mut self, button: Button, now: Instant) -> Vec<Event> {
+        let mut events = self.expire(now);
+
+        if let Some((other, at)) = self.pending.take() {
+            if other != button {
+                if let Some(chord) = self.chord(other, button) {
+                    self.last = None;
+                    events.push(chord);
+                    return events;
+                }
+            }
+            events.push(self.single(other, at));
+        }
+        if self.config.chord > 0 && self.in_chord(button) {
+            self.pending = Some((button, now));
+        } else {
+            events.push(self.single(button, now));
+        }
+
+        events
+    }
+
+    /// Returns the time at which `expire` must be called to pass on a press
+    /// held back for a chord.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.map(|(_, at)| at + ms(self.config.chord))
+    }
+
+    /// Returns the events for a press held back for a chord, once the chord
+    /// time has passed.
+    pub fn expire(&mut self, now: Instant) -> Vec<Event> {
+        match self.pending {
+            Some((button, at)) if now >= at + ms(self.config.chord) => {
+                self.pending = None;
+                vec![self.single(button, at)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the event for a press of `button` at `at` which is not part
+    /// of a chord.
+    fn single(&mut self, button: Button, at: Instant) -> Event {
+        match self.last {
+            Some((last, before))
+                if last == button
+                    && self.config.double > 0
+                    && at - before <= ms(self.config.double) =>
+            {
+                self.last = None;
+                Event::Double(button)
+            }
+            _ => {
+                self.last = Some((button, at));
+                Event::from(button)
+            }
+        }
+    }
+
+    /// Returns the chord formed by `first` and `second`, if any.
+    fn chord(&self, first: Button, second: Button) -> Option<Event> {
+        let pair = (first.min(second), first.max(second));
+        self.chords
+            .iter()
+            .find(|&&(a, b)| (a.min(b), a.max(b)) == pair)
+            .map(|_| Event::Chord(pair.0, pair.1))
+    }
+
+    fn in_chord(&self, button: Button) -> bool {
+        self.chords.iter().any(|&(a, b)| a == button || b == button)
+    }
+}
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHORDS: &[(Button, Button)] = &[(Button::A, Button::B)];
+
+    fn recognizer(chords: &[(Button, Button)]) -> Recognizer {
+        Recognizer::new(
+            Gestures {
+                double: 400,
+                chord: 100,
+            },
+            chords,
+        )
+    }
+
+    #[test]
+    fn presses_pass_straight_through_without_chords() {
+        let mut gestures = recognizer(&[]);
+        let now = Instant::now();
+        assert_eq!(gestures.press(Button::A, now), vec![Event::A]);
+        assert_eq!(gestures.press(Button::B, now), vec![Event::B]);
+        assert_eq!(gestures.deadline(), None);
+    }
+
+    #[test]
+    fn other_buttons_are_not_held_back() {
+        let mut gestures = recognizer(CHORDS);
+        assert_eq!(gestures.press(Button::Up, Instant::now()), vec![Event::Up]);
+        assert_eq!(gestures.deadline(), None);
+    }
+
+    #[test]
+    fn chord_buttons_pressed_together() {
+        let mut gestures = recognizer(CHORDS);
+        let now = Instant::now();
+        assert_eq!(gestures.press(Button::B, now), Vec::new());
+        assert_eq!(gestures.deadline(), Some(now + ms(100)));
+        assert_eq!(
+            gestures.press(Button::A, now + ms(50)),
+            vec![Event::Chord(Button::A, Button::B)]
+        );
+        assert_eq!(gestures.deadline(), None);
+    }
+
+    #[test]
+    fn chord_button_is_passed_on_after_the_chord_time() {
+        let mut gestures = recognizer(CHORDS);
+        let now = Instant::now();
+        gestures.press(Button::A, now);
+        assert_eq!(gestures.expire(now + ms(99)), Vec::new());
+        assert_eq!(gestures.expire(now + ms(100)), vec![Event::A]);
+        assert_eq!(gestures.expire(now + ms(200)), Vec::new());
+    }
+
+    #[test]
+    fn late_second_press_is_not_a_chord() {
+        let mut gestures = recognizer(CHORDS);
+        let now = Instant::now();
+        gestures.press(Button::A, now);
+        assert_eq!(gestures.press(Button::B, now + ms(150)), vec![Event::A]);
+        assert_eq!(gestures.expire(now + ms(250)), vec![Event::B]);
+    }
+
+    #[test]
+    fn held_back_press_is_passed_on_before_the_next() {
+        let mut gestures = recognizer(CHORDS);
+        let now = Instant::now();
+        gestures.press(Button::A, now);
+        assert_eq!(
+            gestures.press(Button::Down, now + ms(10)),
+            vec![Event::A, Event::Down]
+        );
+        gestures.press(Button::A, now + ms(20));
+        assert_eq!(gestures.press(Button::A, now + ms(30)), vec![Event::A]);
+        assert_eq!(
+            gestures.expire(now + ms(130)),
+            vec![Event::Double(Button::A)]
+        );
+    }
+
+    #[test]
+    fn second_press_within_the_double_time_is_a_double_press() {
+        let mut gestures = recognizer(CHORDS);
+        let now = Instant::now();
+        assert_eq!(gestures.press(Button::Up, now), vec![Event::Up]);
+        assert_eq!(
+            gestures.press(Button::Up, now + ms(400)),
+            vec![Event::Double(Button::Up)]
+        );
+        assert_eq!(gestures.press(Button::Up, now + ms(500)), vec![Event::Up]);
+        assert_eq!(gestures.press(Button::Up, now + ms(1000)), vec![Event::Up]);
+    }
+
+    #[test]
+    fn double_press_of_a_chord_button_is_timed_from_the_presses() {
+        let mut gestures = recognizer(CHORDS);
+        let now = Instant::now();
+        gestures.press(Button::B, now);
+        assert_eq!(gestures.expire(now + ms(100)), vec![Event::B]);
+        gestures.press(Button::B, now + ms(350));
+        assert_eq!(
+            gestures.expire(now + ms(450)),
+            vec![Event::Double(Button::B)]
+        );
+    }
+
+    #[test]
+    fn other_presses_in_between_are_not_a_double_press() {
+        let mut gestures = recognizer(&[]);
+        let now = Instant::now();
+        gestures.press(Button::Down, now);
+        gestures.press(Button::Up, now + ms(100));
+        assert_eq!(
+            gestures.press(Button::Down, now + ms(200)),
+            vec![Event::Down]
+        );
+    }
+
+    #[test]
+    fn chord_is_not_the_first_press_of_a_double_press() {
+        let mut gestures = recognizer(CHORDS);
+        let now = Instant::now();
+        gestures.press(Button::A, now);
+        gestures.press(Button::B, now + ms(10));
+        gestures.press(Button::B, now + ms(200));
+        assert_eq!(gestures.expire(now + ms(300)), vec![Event::B]);
+    }
+
+    #[test]
+    fn zero_double_time_disables_double_presses() {
+        let mut gestures = Recognizer::new(
+            Gestures {
+                double: 0,
+                chord: 100,
+            },
+            &[],
+        );
+        let now = Instant::now();
+        gestures.press(Button::A, now);
+        assert_eq!(gestures.press(Button::A, now), vec![Event::A]);
+    }
+
+    #[test]
+    fn zero_chord_time_disables_chords() {
+        let mut gestures = Recognizer::new(
+            Gestures {
+                double: 400,
+                chord: 0,
+            },
+            CHORDS,
+        );
+        assert_eq!(gestures.press(Button::A, Instant::now()), vec![Event::A]);
+    }
+}
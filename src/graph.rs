@@ -0,0 +1,49 @@
+//! Graphs of recent samples, drawn as column charts.
+
+use std::collections::VecDeque;
+
+use crate::bitmap::Bitmap;
+
+/// A ring buffer of recent samples of one value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+    samples: VecDeque<u64>,
+    capacity: usize,
+    floor: u64,
+}
+
+impl Graph {
+    /// Returns an empty graph which keeps the last `capacity` samples.
+    ///
+    /// The graph is scaled to its largest sample, but never to less than
+    /// `floor`, so that small values are not drawn at full height.
+    pub fn new(capacity: usize, floor: u64) -> Graph {
+        Graph {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            floor: floor.max(1),
+        }
+    }
+
+    /// Adds `sample`, dropping the oldest sample when the graph is full.
+    pub fn push(&mut self, sample: u64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Returns the most recent sample.
+    pub fn last(&self) -> Option<u64> {
+        self.samples.back().copied()
+    }
+
+    /// Returns the most recent samples which fit in `width` pixels as a
+    /// column chart, newest on the right.
+    pub fn bitmap(&self, width: u32, height: u32) -> Bitmap {
+        let skip = self.samples.len().saturating_sub(width as usize);
+        let shown: Vec<u64> = self.samples.iter().skip(skip).copied().collect();
+        let max = shown.iter().copied().fold(self.floor, u64::max);
+        Bitmap::sparkline(&shown, max, width, height)
+    }
+}
@@ -0,0 +1,294 @@
+use std::fmt;
+
+use crate::state_machine::Event;
+
+/// The characters on each page of the keyboard, one string per row.
+const LOWER: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+const UPPER: [&str; 4] = ["1234567890", "QWERTYUIOP", "ASDFGHJKL", "ZXCVBNM"];
+const SYMBOLS: [&str; 4] = ["!@#$%^&*()", "-_=+[]{};:", "'\"\\|,.<>/?", "`~ "];
+
+/// The number of keys on the bottom row: shift, symbols and done.
+const SPECIAL_KEYS: usize = 3;
+
+/// Text which is kept out of the log.
+#[derive(Clone, Default, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(text: String) -> Self {
+        Secret(text)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret({} chars)", self.0.chars().count())
+    }
+}
+
+/// The character sets which may be shown on the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Page {
+    Lower,
+    Upper,
+    Symbols,
+}
+
+/// The result of pressing a button on the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The text or cursor changed, or nothing happened.
+    Typing,
+    /// The text was submitted with Center or the done key, and is long
+    /// enough.
+    Submit,
+    /// B was pressed with no text entered.
+    Cancel,
+}
+
+/// A character-grid keyboard for entering text with the D-pad.
+///
+/// The arrows move the cursor over the keys, A types the highlighted key,
+/// B deletes the last character and Center submits the text. Below the
+/// characters is a row of special keys which switch between the lower case,
+/// upper case and symbol pages or submit the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyboard {
+    text: Secret,
+    min_len: usize,
+    max_len: usize,
+    /// The last press submitted text which was too short.
+    rejected: bool,
+    page: Page,
+    row: usize,
+    col: usize,
+}
+
+impl Keyboard {
+    /// Returns an empty keyboard which accepts up to `max_len` characters
+    /// and submits at least `min_len`.
+    pub fn new(min_len: usize, max_len: usize) -> Keyboard {
+        Keyboard {
+            text: Secret::default(),
+            min_len,
+            max_len,
+            rejected: false,
+            page: Page::Lower,
+            row: 0,
+            col: 0,
+        }
+    }
+
+    /// Returns the text entered so far.
+    pub fn text(&self) -> &Secret {
+        &self.text
+    }
+
+    /// Returns the text entered so far with every character but the last
+    /// replaced by `*`.
+    pub fn masked(&self) -> String {
+        let len = self.text.0.chars().count();
+        self.text
+            .0
+            .chars()
+            .enumerate()
+            .map(|(i, c)| if i + 1 == len { c } else { '*' })
+            .collect()
+    }
+
+    pub fn page(&self) -> Page {
+        self.page
+    }
+
+    /// Returns the accepted number of characters, if the last press
+    /// submitted too few.
+    pub fn rejected(&self) -> Option<(usize, usize)> {
+        if self.rejected {
+            Some((self.min_len, self.max_len))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the rows of characters on the current page.
+    pub fn rows(&self) -> &'static [&'static str] {
+        match self.page {
+            Page::Lower => &LOWER,
+            Page::Upper => &UPPER,
+            Page::Symbols => &SYMBOLS,
+        }
+    }
+
+    /// Returns the row and column of the highlighted key. The row below the
+    /// characters holds the special keys.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Returns the number of keys on `row`.
+    fn len(&self, row: usize) -> usize {
+        match self.rows().get(row) {
+            Some(keys) => keys.len(),
+            None => SPECIAL_KEYS,
+        }
+    }
+
+    /// Updates the keyboard for a button press.
+    pub fn press(&mut self, event: Event) -> Outcome {
+        let outcome = self.update(event);
+        self.rejected = outcome == Outcome::Submit && self.text.0.chars().count() < self.min_len;
+        if self.rejected {
+            Outcome::Typing
+        } else {
+            outcome
+        }
+    }
+
+    fn update(&mut self, event: Event) -> Outcome {
+        let rows = self.rows().len() + 1;
+        match event {
+            Event::Up => {
+                self.row = (self.row + rows - 1) % rows;
+                self.col = self.col.min(self.len(self.row) - 1);
+            }
+            Event::Down => {
+                self.row = (self.row + 1) % rows;
+                self.col = self.col.min(self.len(self.row) - 1);
+            }
+            Event::Left => {
+                let len = self.len(self.row);
+                self.col = (self.col + len - 1) % len;
+            }
+            Event::Right => self.col = (self.col + 1) % self.len(self.row),
+            Event::A => match self.rows().get(self.row) {
+                Some(keys) => {
+                    if self.text.0.chars().count() < self.max_len {
+                        if let Some(c) = keys.chars().nth(self.col) {
+                            self.text.0.push(c);
+                        }
+                    }
+                }
+                None => match self.col {
+                    0 => {
+                        self.page = match self.page {
+                            Page::Lower => Page::Upper,
+                            _ => Page::Lower,
+                        }
+                    }
+                    1 => {
+                        self.page = match self.page {
+                            Page::Symbols => Page::Lower,
+                            _ => Page::Symbols,
+                        }
+                    }
+                    _ => return Outcome::Submit,
+                },
+            },
+            Event::B => {
+                if self.text.0.pop().is_none() {
+                    return Outcome::Cancel;
+                }
+            }
+            Event::Center => return Outcome::Submit,
+            Event::Double(_) | Event::Chord(..) | Event::Unknown => (),
+        }
+        Outcome::Typing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Types the characters of `text` found on the lower case page.
+    fn type_text(keyboard: &mut Keyboard, text: &str) {
+        for c in text.chars() {
+            let (row, col) = LOWER
+                .iter()
+                .enumerate()
+                .find_map(|(row, keys)| keys.find(c).map(|col| (row, col)))
+                .unwrap();
+            keyboard.row = row;
+            keyboard.col = col;
+            assert_eq!(keyboard.press(Event::A), Outcome::Typing);
+        }
+    }
+
+    #[test]
+    fn short_text_is_rejected() {
+        let mut keyboard = Keyboard::new(8, 63);
+        type_text(&mut keyboard, "peach");
+        assert_eq!(keyboard.press(Event::Center), Outcome::Typing);
+        assert_eq!(keyboard.rejected(), Some((8, 63)));
+        type_text(&mut keyboard, "123");
+        assert_eq!(keyboard.rejected(), None);
+        assert_eq!(keyboard.press(Event::Center), Outcome::Submit);
+        assert_eq!(keyboard.text().as_str(), "peach123");
+    }
+
+    #[test]
+    fn text_stops_at_max_len() {
+        let mut keyboard = Keyboard::new(0, 3);
+        type_text(&mut keyboard, "abcd");
+        assert_eq!(keyboard.text().as_str(), "abc");
+        assert_eq!(keyboard.masked(), "**c");
+    }
+
+    #[test]
+    fn b_deletes_then_cancels() {
+        let mut keyboard = Keyboard::new(0, 63);
+        type_text(&mut keyboard, "ab");
+        assert_eq!(keyboard.press(Event::B), Outcome::Typing);
+        assert_eq!(keyboard.text().as_str(), "a");
+        assert_eq!(keyboard.press(Event::B), Outcome::Typing);
+        assert_eq!(keyboard.press(Event::B), Outcome::Cancel);
+    }
+
+    #[test]
+    fn cursor_wraps_around() {
+        let mut keyboard = Keyboard::new(0, 63);
+        keyboard.press(Event::Left);
+        assert_eq!(keyboard.cursor(), (0, 9));
+        keyboard.press(Event::Right);
+        assert_eq!(keyboard.cursor(), (0, 0));
+        keyboard.press(Event::Up);
+        assert_eq!(keyboard.cursor(), (4, 0));
+        keyboard.press(Event::Down);
+        assert_eq!(keyboard.cursor(), (0, 0));
+    }
+
+    #[test]
+    fn cursor_stays_on_shorter_rows() {
+        let mut keyboard = Keyboard::new(0, 63);
+        keyboard.press(Event::Left);
+        keyboard.press(Event::Up);
+        assert_eq!(keyboard.cursor(), (4, SPECIAL_KEYS - 1));
+    }
+
+    #[test]
+    fn special_keys_switch_pages_and_submit() {
+        let mut keyboard = Keyboard::new(0, 63);
+        keyboard.press(Event::Up);
+        keyboard.press(Event::A);
+        assert_eq!(keyboard.page(), Page::Upper);
+        keyboard.press(Event::Right);
+        keyboard.press(Event::A);
+        assert_eq!(keyboard.page(), Page::Symbols);
+        keyboard.press(Event::A);
+        assert_eq!(keyboard.page(), Page::Lower);
+        keyboard.press(Event::Right);
+        assert_eq!(keyboard.press(Event::A), Outcome::Submit);
+    }
+
+    #[test]
+    fn secret_is_not_logged() {
+        let secret = Secret::from("cloudpeach".to_string());
+        assert_eq!(format!("{:?}", secret), "Secret(10 chars)");
+    }
+}
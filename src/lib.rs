@@ -5,43 +5,140 @@
 //! with `peach-buttons` providing GPIO input data and `peach-oled` receiving
 //! output data for display.
 //!
+mod bitmap;
+mod burn_in;
 pub mod buttons;
+pub mod config;
+pub mod display;
+pub mod error;
+mod font;
+mod format;
+mod gestures;
+mod graph;
+pub mod keyboard;
+pub mod menu;
+pub mod monitor;
+pub mod network;
+pub mod power;
+mod qr;
+pub mod render;
+pub mod server;
+pub mod services;
 pub mod state_machine;
 mod states;
-mod structs;
+pub mod structs;
+pub mod throughput;
+pub mod wifi;
 
-use std::env;
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::{env, thread};
 
-use crossbeam_channel::unbounded;
-use log::{debug, info};
-use ws::connect;
+use crossbeam_channel::{unbounded, Sender};
+use log::{debug, info, warn};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
 
 use crate::buttons::*;
+use crate::config::Config;
+use crate::display::Oled;
+use crate::error::MenuError;
+use crate::monitor::Monitor;
+use crate::render::Renderer;
+use crate::services::Peach;
 use crate::state_machine::*;
 
-/// Configures channels for message passing, launches the state machine
-/// changer thread and connects to the `peach-buttons` JSON-RPC pubsub
-/// service over websockets.
+/// Loads the configuration, configures channels for message passing,
+/// launches the state machine changer thread, starts the control server and
+/// connects to the `peach-buttons` JSON-RPC pubsub service over websockets.
 ///
 /// A Receiver is passed into `state_changer` and the corresponding Sender
 /// is passed into the websockets client. This allows the `button_code` to
 /// be extracted from the received websocket message and passed to the
 /// state machine.
 ///
-pub fn run() -> std::result::Result<(), Box<dyn std::error::Error>> {
+/// If `peach-buttons` is unavailable or the connection is lost, the client
+/// reconnects with exponential backoff. The state machine thread keeps
+/// running throughout and is notified of connection changes so that it can
+/// display an offline indicator.
+///
+/// A clone of the Sender is passed into the JSON-RPC control server, which
+/// injects events and state jumps through the same channel.
+///
+/// `SIGTERM` and `SIGINT` stop the state machine. Returns once the state
+/// machine thread has stopped.
+///
+pub fn run() -> Result<(), MenuError> {
     info!("Starting up.");
 
+    let config = Config::load()?;
+
     debug!("Creating unbounded channel for message passing.");
     let (s, r) = unbounded();
 
+    let menus = config.menus.keys().cloned().collect();
+
     debug!("Spawning state-machine thread.");
-    state_changer(r);
+    let current = Arc::new(Mutex::new(State::Logo));
+    let display = Renderer::new(Oled, config.burn_in.clone());
+    let monitor = Monitor::new(&config.network.interfaces);
+    let ctx = Context {
+        config,
+        display: Box::new(display),
+        services: Box::new(Peach),
+        monitor,
+    };
+    let state_machine = state_changer(r, ctx, Arc::clone(&current));
+
+    let server_addr =
+        env::var("PEACH_MENU_SERVER").unwrap_or_else(|_| "127.0.0.1:5115".to_string());
+    let _server = server::start(&server_addr, s.clone(), current, menus)?;
 
     let ws_addr = env::var("PEACH_BUTTONS_SERVER").unwrap_or_else(|_| "127.0.0.1:5111".to_string());
 
     let ws_server = format!("ws://{}", ws_addr);
 
-    connect(ws_server, |out| Client { out, s: &s })?;
+    let mut signals = Signals::new([SIGTERM, SIGINT])?;
+    let stop = s.clone();
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            info!("Received signal {}.", signal);
+            let _ = stop.send(Input::Shutdown);
+        }
+    });
+
+    debug!("Spawning peach_buttons client thread.");
+    thread::spawn(move || buttons_client(&ws_server, s));
+
+    state_machine
+        .join()
+        .map_err(|_| MenuError::Channel("state machine thread panicked".to_string()))?;
+    info!("State machine has stopped. Shutting down.");
 
     Ok(())
 }
+
+/// Connects to `peach_buttons` at `ws_server` and reconnects with
+/// exponential backoff whenever the connection is lost, until the state
+/// machine stops receiving input.
+fn buttons_client(ws_server: &str, s: Sender<Input>) {
+    let mut backoff = Backoff::new();
+
+    loop {
+        let opened = Cell::new(false);
+        if let Err(e) = connect(ws_server, &s, &opened) {
+            warn!("Failed to connect to peach_buttons at {}: {}", ws_server, e);
+        }
+
+        if opened.get() {
+            backoff.reset();
+        }
+        if s.send(Input::ButtonsOffline).is_err() {
+            break;
+        }
+
+        let delay = backoff.next_delay();
+        info!("Reconnecting to peach_buttons in {} secs.", delay.as_secs());
+        thread::sleep(delay);
+    }
+}
@@ -8,7 +8,7 @@ fn main() {
 
     // handle errors returned from `run`
     if let Err(e) = peach_menu::run() {
-        error!("Application error: {:?}", e);
+        error!("Application error: {}", e);
         process::exit(1);
     }
 }
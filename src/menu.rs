@@ -0,0 +1,177 @@
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::state_machine::{Event, State};
+
+/// The identifier of the menu opened from the network screen.
+pub const NETWORK_CONF_MENU: &str = "network_conf";
+
+/// The identifier of the menu opened by pressing A and B together.
+pub const MAINTENANCE_MENU: &str = "maintenance";
+
+/// A menu declared in the configuration file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Menu {
+    /// Heading drawn on the first line.
+    pub title: Option<String>,
+    /// Draw the current time in the top-right corner.
+    #[serde(default)]
+    pub clock: bool,
+    /// Text drawn right-aligned on the last line.
+    pub footer: Option<String>,
+    /// Target selected with the B button.
+    pub back: Option<Target>,
+    pub items: Vec<Item>,
+}
+
+/// A selectable menu entry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Item {
+    pub label: String,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+/// The destination of a menu item or back button.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    Menu(String),
+    Screen(Screen),
+    Action(Action),
+}
+
+/// The built-in screens which may be opened from a menu.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Screen {
+    Logo,
+    Network,
+    Qr,
+    Saved,
+    Scan,
+    Stats,
+    Storage,
+}
+
+/// The built-in actions which may be run from a menu.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    DisplayOff,
+    Reboot,
+    Shutdown,
+    ClientMode,
+    AccessPointMode,
+}
+
+/// What the Center, Left and Right buttons do outside of the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    /// Select, like A.
+    Select,
+    /// Go back, like B.
+    Back,
+    /// Open the selected menu or screen, or show the next page.
+    Forward,
+    /// Do nothing.
+    None,
+}
+
+impl Target {
+    /// Returns the state which this target leads to.
+    pub fn state(&self) -> Option<State> {
+        let state = match *self {
+            Target::Menu(ref id) => State::Menu(id.to_string(), 0),
+            Target::Screen(Screen::Logo) => State::Logo,
+            Target::Screen(Screen::Network) => State::Network(0),
+            Target::Screen(Screen::Qr) => State::Qr(0),
+            Target::Screen(Screen::Saved) => State::Saved(Vec::new(), 0),
+            Target::Screen(Screen::Scan) => State::Scan(Vec::new(), 0),
+            Target::Screen(Screen::Stats) => State::Stats(0),
+            Target::Screen(Screen::Storage) => State::Storage(Vec::new(), 0),
+            Target::Action(action) => return action.state(),
+        };
+        Some(state)
+    }
+}
+
+impl Action {
+    /// Returns the state which runs this action, if any. Actions without a
+    /// state, such as turning the display off, are run by `state_changer`
+    /// and leave the current state unchanged.
+    pub fn state(self) -> Option<State> {
+        match self {
+            Action::DisplayOff => None,
+            Action::Reboot => Some(State::Reboot),
+            Action::Shutdown => Some(State::Shutdown),
+            Action::ClientMode => Some(State::NetworkMode(0)),
+            Action::AccessPointMode => Some(State::NetworkMode(1)),
+        }
+    }
+}
+
+impl Menu {
+    /// Returns the index of the first item leading to `target`.
+    pub fn position(&self, target: &Target) -> Option<usize> {
+        self.items.iter().position(|item| item.target == *target)
+    }
+}
+
+/// Determines the next state for the menu `id` with the item at index
+/// `selected` highlighted.
+///
+/// Up and Down move the selection, wrapping around at either end. A follows
+/// the target of the selected item and B follows the back target of the menu.
+/// Actions which must be confirmed, whether selected or reached with B, open
+/// a confirmation dialog which returns to this menu if cancelled. Actions
+/// without a state leave the menu unchanged; see `selected_action`.
+pub fn navigate(config: &Config, id: String, selected: usize, event: Event) -> State {
+    let menu = match config.menus.get(&id) {
+        Some(menu) => menu,
+        None => return State::Menu(id, selected),
+    };
+    let len = menu.items.len();
+    match event {
+        Event::Down => State::Menu(id, (selected + 1) % len),
+        Event::Up => State::Menu(id, (selected + len - 1) % len),
+        Event::A => match menu.items.get(selected) {
+            Some(item) => match item.target.state() {
+                Some(next) => confirmed(config, next, State::Menu(id, selected)),
+                None => State::Menu(id, selected),
+            },
+            None => State::Menu(id, 0),
+        },
+        Event::B => match menu.back.as_ref().and_then(Target::state) {
+            Some(next) => confirmed(config, next, State::Menu(id, selected)),
+            None => State::Menu(id, selected),
+        },
+        _ => State::Menu(id, selected),
+    }
+}
+
+/// Returns the action without a state which `event` selects in the menu `id`
+/// with the item at index `selected` highlighted, if any.
+pub fn selected_action(config: &Config, id: &str, selected: usize, event: Event) -> Option<Action> {
+    let menu = config.menus.get(id)?;
+    let target = match event {
+        Event::A => &menu.items.get(selected)?.target,
+        Event::B => menu.back.as_ref()?,
+        _ => return None,
+    };
+    match *target {
+        Target::Action(action) if action.state().is_none() => Some(action),
+        _ => None,
+    }
+}
+
+/// Returns a confirmation dialog for `next` which returns to `from` if
+/// cancelled, or `next` itself if it needs no confirmation.
+fn confirmed(config: &Config, next: State, from: State) -> State {
+    if next.prompt().is_some() {
+        State::Confirm(Box::new(next), config.confirm.timeout, Box::new(from))
+    } else {
+        next
+    }
+}
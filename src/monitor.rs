@@ -0,0 +1,123 @@
+//! Recent system and network activity, sampled in the background.
+
+use std::time::Instant;
+
+use log::debug;
+
+use crate::graph::Graph;
+use crate::network::{Interface, Operstate};
+use crate::services::Services;
+use crate::throughput::{Rate, Throughput};
+
+/// The number of samples kept for each graph. This covers four minutes at
+/// the default Stats refresh interval.
+pub const HISTORY_LEN: usize = 120;
+
+/// Graphs of CPU usage, load and throughput.
+///
+/// Throughput is measured on the first of the configured interfaces which is
+/// active when the sample is taken, so that it follows a switch between
+/// client and access point mode or a cable being plugged in.
+#[derive(Debug)]
+pub struct Monitor {
+    ifaces: Vec<String>,
+    /// The interface throughput was last measured on.
+    iface: Option<String>,
+    throughput: Throughput,
+    /// CPU time not spent idle, in percent.
+    pub cpu: Graph,
+    /// One minute load average, in hundredths.
+    pub load: Graph,
+    /// Bytes per second received.
+    pub received: Graph,
+    /// Bytes per second transmitted.
+    pub transmitted: Graph,
+}
+
+impl Monitor {
+    /// Returns a monitor with no samples, which measures throughput on
+    /// `interfaces`.
+    pub fn new(interfaces: &[Interface]) -> Monitor {
+        Monitor {
+            ifaces: interfaces.iter().map(|iface| iface.name.clone()).collect(),
+            iface: None,
+            throughput: Throughput::new(),
+            cpu: Graph::new(HISTORY_LEN, 100),
+            load: Graph::new(HISTORY_LEN, 100),
+            received: Graph::new(HISTORY_LEN, 1024),
+            transmitted: Graph::new(HISTORY_LEN, 1024),
+        }
+    }
+
+    /// Returns the interface throughput was last measured on, if any was
+    /// active.
+    pub fn iface(&self) -> Option<&str> {
+        self.iface.as_deref()
+    }
+
+    /// Returns the most recent throughput.
+    pub fn rate(&self) -> Option<Rate> {
+        self.throughput.rate(self.iface()?)
+    }
+
+    /// Adds a sample of each value to its graph. Values which cannot be read
+    /// are skipped until the next sample.
+    pub fn sample(&mut self, services: &dyn Services) {
+        match services.cpu_stats_percent() {
+            Ok(cpu) => self.cpu.push((100.0 - cpu.idle).round().max(0.0) as u64),
+            Err(e) => debug!("Failed to sample CPU usage: {:?}", e),
+        }
+        match services.load_average() {
+            Ok(load) => self.load.push((load.one * 100.0).round().max(0.0) as u64),
+            Err(e) => debug!("Failed to sample load average: {:?}", e),
+        }
+        self.iface = self.active(services);
+        let iface = match self.iface {
+            Some(ref iface) => iface,
+            None => {
+                debug!("No active interface to sample traffic on");
+                return;
+            }
+        };
+        match services.traffic(iface) {
+            Ok(traffic) => {
+                let rate = self.throughput.record(iface, traffic, Instant::now());
+                if let Some(rate) = rate {
+                    self.received.push(rate.received);
+                    self.transmitted.push(rate.transmitted);
+                }
+            }
+            Err(e) => debug!("Failed to sample traffic on {}: {:?}", iface, e),
+        }
+    }
+
+    /// Returns the first configured interface which is active.
+    fn active(&self, services: &dyn Services) -> Option<String> {
+        self.ifaces
+            .iter()
+            .find(|iface| match services.state(iface) {
+                Ok(state) => Operstate::parse(&state).is_active(),
+                Err(_) => false,
+            })
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::services::Simulated;
+
+    #[test]
+    fn throughput_follows_the_active_interface() {
+        let services = Simulated::new();
+        let mut monitor = Monitor::new(&Config::default().network.interfaces);
+        assert_eq!(monitor.iface(), None);
+        monitor.sample(&services);
+        assert_eq!(monitor.iface(), Some("wlan0"));
+        services.activate_ap().unwrap();
+        monitor.sample(&services);
+        assert_eq!(monitor.iface(), Some("ap0"));
+    }
+}
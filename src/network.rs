@@ -0,0 +1,277 @@
+//! The network interfaces shown on the Network screen.
+
+use log::debug;
+use serde::Deserialize;
+
+use crate::config::Ap;
+use crate::keyboard::Secret;
+use crate::services::Services;
+
+/// What a network interface is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// A wired connection.
+    Ethernet,
+    /// A wireless connection to another access point.
+    Client,
+    /// The access point hosted by the device.
+    Ap,
+    /// A USB gadget connection to a computer.
+    Usb,
+}
+
+impl Role {
+    pub fn label(self) -> &'static str {
+        match self {
+            Role::Ethernet => "Ethernet",
+            Role::Client => "Wi-Fi client",
+            Role::Ap => "Access point",
+            Role::Usb => "USB",
+        }
+    }
+}
+
+/// A network interface declared in the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Interface {
+    pub name: String,
+    pub role: Role,
+}
+
+/// The operational state of an interface, as reported by the kernel in
+/// `/sys/class/net/<iface>/operstate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operstate {
+    Up,
+    Down,
+    /// Waiting for an external event, such as Wi-Fi authentication.
+    Dormant,
+    /// Down because the interface it is stacked on is down.
+    LowerLayerDown,
+    NotPresent,
+    Testing,
+    /// Not tracked by the driver. Common for virtual and USB interfaces,
+    /// which may still carry traffic.
+    Unknown,
+    /// The state could not be read.
+    Error,
+}
+
+impl Operstate {
+    pub fn parse(state: &str) -> Operstate {
+        match state.trim() {
+            "up" => Operstate::Up,
+            "down" => Operstate::Down,
+            "dormant" => Operstate::Dormant,
+            "lowerlayerdown" => Operstate::LowerLayerDown,
+            "notpresent" => Operstate::NotPresent,
+            "testing" => Operstate::Testing,
+            _ => Operstate::Unknown,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Operstate::Up => "up",
+            Operstate::Down => "down",
+            Operstate::Dormant => "dormant",
+            Operstate::LowerLayerDown => "no link",
+            Operstate::NotPresent => "not present",
+            Operstate::Testing => "testing",
+            Operstate::Unknown => "unknown",
+            Operstate::Error => "error",
+        }
+    }
+
+    /// Returns whether the interface may have an address and carry traffic.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            *self,
+            Operstate::Up | Operstate::Dormant | Operstate::Unknown
+        )
+    }
+}
+
+/// The current state and address of an interface.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub name: String,
+    pub role: Role,
+    pub state: Operstate,
+    pub ip: Option<String>,
+}
+
+/// Returns the current state and address of `iface`.
+///
+/// The address is only looked up while the interface is active.
+pub fn status(services: &dyn Services, iface: &Interface) -> Status {
+    let state = match services.state(&iface.name) {
+        Ok(state) => Operstate::parse(&state),
+        Err(_) => Operstate::Error,
+    };
+    let ip = if state.is_active() {
+        services.ip(&iface.name).ok().filter(|ip| !ip.is_empty())
+    } else {
+        None
+    };
+    Status {
+        name: iface.name.to_string(),
+        role: iface.role,
+        state,
+        ip,
+    }
+}
+
+/// The settings of the access point hosted by `hostapd`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApSettings {
+    /// `None` if unknown.
+    pub ssid: Option<String>,
+    pub channel: Option<u32>,
+    /// Empty for an open network, and `None` if unknown.
+    pub passphrase: Option<Secret>,
+}
+
+/// Returns the settings of the access point, read from the `hostapd`
+/// configuration.
+///
+/// The settings in the `[ap]` section of the configuration are used when
+/// the `hostapd` configuration cannot be read. Settings found in neither
+/// are unknown.
+pub fn ap_settings(services: &dyn Services, ap: &Ap) -> ApSettings {
+    match services.hostapd_config() {
+        Ok(contents) => parse_hostapd(&contents, ap),
+        Err(e) => {
+            debug!("Failed to read the hostapd configuration: {:?}", e);
+            ApSettings {
+                ssid: ap.ssid.clone(),
+                channel: None,
+                passphrase: ap.passphrase.clone().map(Secret::from),
+            }
+        }
+    }
+}
+
+/// Returns the settings in the `hostapd` configuration `contents`, taking
+/// the SSID from `ap` if none is set.
+///
+/// A network without WPA is open. The passphrase of a WPA network is
+/// unknown if only its key (`wpa_psk`) is set.
+fn parse_hostapd(contents: &str, ap: &Ap) -> ApSettings {
+    let mut settings = ApSettings {
+        ssid: ap.ssid.clone(),
+        channel: None,
+        passphrase: None,
+    };
+    let mut wpa = false;
+    for line in contents.lines().map(str::trim) {
+        match line.split_once('=') {
+            Some(("ssid", ssid)) => settings.ssid = Some(ssid.to_string()),
+            Some(("channel", channel)) => settings.channel = channel.parse().ok(),
+            Some(("wpa", wpa_mode)) => wpa = wpa_mode != "0",
+            Some(("wpa_passphrase", passphrase)) => {
+                settings.passphrase = Some(Secret::from(passphrase.to_string()))
+            }
+            _ => (),
+        }
+    }
+    if !wpa {
+        settings.passphrase = Some(Secret::default());
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::Simulated;
+
+    fn secret(text: &str) -> Option<Secret> {
+        Some(Secret::from(text.to_string()))
+    }
+
+    #[test]
+    fn operstates_are_parsed() {
+        assert_eq!(Operstate::parse("up\n"), Operstate::Up);
+        assert_eq!(
+            Operstate::parse("lowerlayerdown"),
+            Operstate::LowerLayerDown
+        );
+        assert_eq!(Operstate::parse("notpresent"), Operstate::NotPresent);
+        assert_eq!(Operstate::parse("unknown"), Operstate::Unknown);
+        assert_eq!(Operstate::parse("UP"), Operstate::Unknown);
+        assert_eq!(Operstate::parse(""), Operstate::Unknown);
+    }
+
+    #[test]
+    fn only_active_interfaces_may_carry_traffic() {
+        assert!(Operstate::Up.is_active());
+        assert!(Operstate::Dormant.is_active());
+        assert!(Operstate::Unknown.is_active());
+        assert!(!Operstate::Down.is_active());
+        assert!(!Operstate::LowerLayerDown.is_active());
+        assert!(!Operstate::Error.is_active());
+    }
+
+    #[test]
+    fn addresses_are_only_looked_up_for_active_interfaces() {
+        let services = Simulated::new();
+        let interface = |name: &str, role| Interface {
+            name: name.to_string(),
+            role,
+        };
+        let wlan = status(&services, &interface("wlan0", Role::Client));
+        assert_eq!(wlan.state, Operstate::Up);
+        assert_eq!(wlan.ip.as_deref(), Some("192.168.1.23"));
+        let eth = status(&services, &interface("eth0", Role::Ethernet));
+        assert_eq!(eth.state, Operstate::LowerLayerDown);
+        assert_eq!(eth.ip, None);
+        let usb = status(&services, &interface("usb0", Role::Usb));
+        assert_eq!(usb.state, Operstate::Unknown);
+        assert_eq!(usb.ip.as_deref(), Some("10.42.0.1"));
+    }
+
+    #[test]
+    fn hostapd_settings_are_read() {
+        let contents = "interface=ap0\nssid=peach\nchannel=6\nwpa=2\nwpa_passphrase=cloudpeach\n";
+        let settings = parse_hostapd(contents, &Ap::default());
+        assert_eq!(settings.ssid.as_deref(), Some("peach"));
+        assert_eq!(settings.channel, Some(6));
+        assert_eq!(settings.passphrase, secret("cloudpeach"));
+    }
+
+    #[test]
+    fn passphrase_may_contain_equals_signs() {
+        let settings = parse_hostapd("wpa=2\nwpa_passphrase=a=b=c\n", &Ap::default());
+        assert_eq!(settings.passphrase, secret("a=b=c"));
+    }
+
+    #[test]
+    fn network_without_wpa_is_open() {
+        let settings = parse_hostapd("ssid=peach\n", &Ap::default());
+        assert_eq!(settings.passphrase, secret(""));
+        let settings = parse_hostapd("ssid=peach\nwpa=0\n", &Ap::default());
+        assert_eq!(settings.passphrase, secret(""));
+    }
+
+    #[test]
+    fn passphrase_of_a_wpa_key_is_unknown() {
+        let settings = parse_hostapd("wpa=2\nwpa_psk=0123456789abcdef\n", &Ap::default());
+        assert_eq!(settings.passphrase, None);
+    }
+
+    #[test]
+    fn missing_ssid_is_taken_from_the_configuration() {
+        let ap = Ap {
+            ssid: Some("configured".to_string()),
+            passphrase: None,
+        };
+        assert_eq!(
+            parse_hostapd("channel=x\n", &ap).ssid.as_deref(),
+            Some("configured")
+        );
+        assert_eq!(parse_hostapd("channel=x\n", &ap).channel, None);
+        assert_eq!(parse_hostapd("", &Ap::default()).ssid, None);
+    }
+}
@@ -0,0 +1,156 @@
+//! Power management of the display.
+//!
+//! The display is turned off from the menu, or blanked when no buttons have
+//! been pressed for a while. `peach-oled` offers no brightness control, so
+//! the display is dimmed by replacing the current state with a small clock,
+//! which lights only a few pixels, before it is powered off.
+//!
+//! Whether the display is on is tracked for the whole state machine rather
+//! than by a state, so the current state is kept while the display is off
+//! and drawn again when it is woken.
+
+use chrono::{DateTime, Duration, Local};
+
+use crate::config::Idle as IdleConfig;
+
+/// What the display shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// The current state.
+    Awake,
+    /// The clock screensaver.
+    Screensaver,
+    /// Nothing: the display is powered off.
+    Off,
+}
+
+/// Tracks whether the display is on and the time since the last button
+/// press.
+#[derive(Debug)]
+pub struct DisplayPower {
+    last_press: DateTime<Local>,
+    phase: Phase,
+}
+
+impl DisplayPower {
+    pub fn new(now: DateTime<Local>) -> DisplayPower {
+        DisplayPower {
+            last_press: now,
+            phase: Phase::Awake,
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Records that the display has been turned off. It stays off until the
+    /// next button press.
+    pub fn off(&mut self) {
+        self.phase = Phase::Off;
+    }
+
+    /// Records a button press at `now` and returns the phase the display was
+    /// in. Presses made while the display is not awake only wake it.
+    pub fn press(&mut self, now: DateTime<Local>) -> Phase {
+        self.last_press = now;
+        let phase = self.phase;
+        self.phase = Phase::Awake;
+        phase
+    }
+
+    /// Returns the phase which the display should enter at `now`, if it has
+    /// changed since the last call.
+    ///
+    /// A timeout of zero disables the corresponding phase.
+    pub fn tick(&mut self, config: &IdleConfig, now: DateTime<Local>) -> Option<Phase> {
+        let idle = now.signed_duration_since(self.last_press);
+        let elapsed = |secs: u64| secs > 0 && idle >= Duration::seconds(secs as i64);
+        let phase = if elapsed(config.off) {
+            Phase::Off
+        } else if elapsed(config.screensaver) {
+            Phase::Screensaver
+        } else {
+            Phase::Awake
+        };
+        // the display stays off until a button is pressed, even if the
+        // timeouts are changed
+        if phase == self.phase || self.phase == Phase::Off {
+            return None;
+        }
+        self.phase = phase;
+        Some(phase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idle(screensaver: u64, off: u64) -> IdleConfig {
+        IdleConfig { screensaver, off }
+    }
+
+    #[test]
+    fn display_dims_then_turns_off_when_idle() {
+        let start = Local::now();
+        let config = idle(120, 600);
+        let mut power = DisplayPower::new(start);
+        assert_eq!(power.tick(&config, start + Duration::seconds(119)), None);
+        assert_eq!(
+            power.tick(&config, start + Duration::seconds(120)),
+            Some(Phase::Screensaver)
+        );
+        assert_eq!(power.tick(&config, start + Duration::seconds(300)), None);
+        assert_eq!(
+            power.tick(&config, start + Duration::seconds(600)),
+            Some(Phase::Off)
+        );
+        assert_eq!(power.tick(&config, start + Duration::seconds(900)), None);
+        assert_eq!(power.phase(), Phase::Off);
+    }
+
+    #[test]
+    fn press_wakes_and_restarts_the_timeouts() {
+        let start = Local::now();
+        let config = idle(120, 600);
+        let mut power = DisplayPower::new(start);
+        power.tick(&config, start + Duration::seconds(200));
+        let pressed = start + Duration::seconds(250);
+        assert_eq!(power.press(pressed), Phase::Screensaver);
+        assert_eq!(power.phase(), Phase::Awake);
+        assert_eq!(power.tick(&config, pressed + Duration::seconds(60)), None);
+        assert_eq!(
+            power.tick(&config, pressed + Duration::seconds(120)),
+            Some(Phase::Screensaver)
+        );
+    }
+
+    #[test]
+    fn zero_timeouts_disable_their_phase() {
+        let start = Local::now();
+        let mut power = DisplayPower::new(start);
+        assert_eq!(
+            power.tick(&idle(0, 600), start + Duration::seconds(300)),
+            None
+        );
+        assert_eq!(
+            power.tick(&idle(0, 600), start + Duration::seconds(600)),
+            Some(Phase::Off)
+        );
+
+        let mut power = DisplayPower::new(start);
+        assert_eq!(power.tick(&idle(0, 0), start + Duration::days(1)), None);
+    }
+
+    #[test]
+    fn display_turned_off_stays_off_until_pressed() {
+        let start = Local::now();
+        let mut power = DisplayPower::new(start);
+        power.off();
+        assert_eq!(power.tick(&idle(120, 600), start), None);
+        assert_eq!(power.phase(), Phase::Off);
+        assert_eq!(power.press(start), Phase::Off);
+        assert_eq!(power.phase(), Phase::Awake);
+    }
+}
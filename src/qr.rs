@@ -0,0 +1,90 @@
+use qrcode::{Color, EcLevel, QrCode};
+
+use crate::bitmap::Bitmap;
+use crate::display::{HEIGHT, WIDTH};
+use crate::error::MenuError;
+
+/// The number of light modules drawn around the code.
+const QUIET_ZONE: usize = 2;
+
+/// Returns the string which, when scanned, offers to join the WPA network
+/// `ssid` using `passphrase`.
+///
+/// Special characters in the SSID and passphrase are escaped with `\`.
+pub fn wifi_join(ssid: &str, passphrase: &str) -> String {
+    if passphrase.is_empty() {
+        format!("WIFI:S:{};T:nopass;;", escape(ssid))
+    } else {
+        format!("WIFI:S:{};T:WPA;P:{};;", escape(ssid), escape(passphrase))
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if let '\\' | ';' | ',' | ':' | '"' = c {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Encodes `data` as a QR code and renders it into a bitmap covering the
+/// whole display.
+///
+/// The code is scaled to fill the square on the left-hand side of the
+/// display as far as possible. Dark modules are left unlit on a lit
+/// background, so that the code can be read by phone cameras.
+pub fn bitmap(data: &str) -> Result<Bitmap, MenuError> {
+    let code = QrCode::with_error_correction_level(data, EcLevel::L)?;
+    let modules = code.width();
+    let size = HEIGHT as usize;
+    if modules + 2 * QUIET_ZONE > size {
+        return Err(MenuError::Qr(qrcode::types::QrError::DataTooLong));
+    }
+    let scale = size / (modules + 2 * QUIET_ZONE);
+    let offset = (size - scale * modules) / 2;
+
+    let mut bitmap = Bitmap::new(WIDTH, HEIGHT);
+    let colors = code.to_colors();
+    for y in 0..size {
+        for x in 0..size {
+            let light = if x < offset || y < offset {
+                true
+            } else {
+                let (mx, my) = ((x - offset) / scale, (y - offset) / scale);
+                mx >= modules || my >= modules || colors[my * modules + mx] == Color::Light
+            };
+            bitmap.set(x as u32, y as u32, light);
+        }
+    }
+
+    Ok(bitmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(escape("Peach"), "Peach");
+        assert_eq!(escape(r#"a;b,c:d"e\f"#), r#"a\;b\,c\:d\"e\\f"#);
+    }
+
+    #[test]
+    fn join_strings() {
+        assert_eq!(
+            wifi_join("Peach;Net", "pass:word"),
+            r"WIFI:S:Peach\;Net;T:WPA;P:pass\:word;;"
+        );
+        assert_eq!(wifi_join("Open", ""), "WIFI:S:Open;T:nopass;;");
+    }
+
+    #[test]
+    fn too_much_data_is_an_error() {
+        assert!(bitmap("http://10.0.0.1").is_ok());
+        assert!(bitmap(&"x".repeat(400)).is_err());
+    }
+}
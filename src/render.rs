@@ -0,0 +1,214 @@
+//! Retained rendering.
+//!
+//! Every call to `peach-oled` is a separate JSON-RPC round trip. `Renderer`
+//! keeps the frame drawn by the states locally and, on `flush`, compares it
+//! with the frame last sent. Only the rectangle containing the changed
+//! pixels is sent, as a single bitmap, and nothing is sent if the frame is
+//! unchanged. Burn-in protection is applied to the frame before it is
+//! compared.
+//!
+//! In case `peach-oled` has lost what it was sent, for example after a
+//! restart, the whole frame is sent every `FULL_INTERVAL` seconds, whenever
+//! the frame is moved or inverted and when the display is turned on.
+
+use chrono::Local;
+
+use crate::bitmap::Bitmap;
+use crate::burn_in::Transform;
+use crate::config::BurnIn;
+use crate::display::{Display, Framebuffer, HEIGHT, WIDTH};
+use crate::error::MenuError;
+
+/// Seconds between sends of the whole frame.
+const FULL_INTERVAL: u64 = 60;
+
+/// Wraps a display, sending it only the changes to each frame.
+#[derive(Debug)]
+pub struct Renderer<D: Display> {
+    display: D,
+    burn_in: BurnIn,
+    /// The frame being drawn by the states.
+    frame: Framebuffer,
+    /// The transform applied to the frame last sent.
+    transform: Option<Transform>,
+    /// What the display shows, if known.
+    sent: Option<Framebuffer>,
+    /// When the whole frame was last sent, in seconds since the epoch.
+    full: u64,
+}
+
+impl<D: Display> Renderer<D> {
+    pub fn new(display: D, burn_in: BurnIn) -> Renderer<D> {
+        Renderer {
+            display,
+            burn_in,
+            frame: Framebuffer::new(),
+            transform: None,
+            sent: None,
+            full: 0,
+        }
+    }
+
+    /// Sends the parts of the frame which differ from what the display
+    /// shows, with `transform` applied.
+    fn send(&mut self, transform: Transform, now: u64) -> Result<(), MenuError> {
+        if self.sent.is_none() {
+            self.full = now;
+        }
+        let frame = transform.apply(&self.frame);
+        self.transform = Some(transform);
+        let (x0, y0, x1, y1) = match changed(self.sent.as_ref(), &frame) {
+            Some(bounds) => bounds,
+            None => return Ok(()),
+        };
+
+        let (width, height) = ((x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32);
+        let mut bitmap = Bitmap::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                bitmap.set(x, y, frame.get(x0 + x as i32, y0 + y as i32));
+            }
+        }
+        // forget what the display shows if it may be partly updated
+        self.sent = None;
+        self.display
+            .draw(bitmap.bytes(), bitmap.width(), bitmap.height(), x0, y0)?;
+        self.display.flush()?;
+        self.sent = Some(frame);
+
+        Ok(())
+    }
+
+    fn now() -> u64 {
+        Local::now().timestamp().max(0) as u64
+    }
+}
+
+/// Returns the smallest rectangle, as inclusive corners, containing every
+/// pixel which differs between `sent` and `frame`. Every pixel differs when
+/// nothing has been sent.
+fn changed(sent: Option<&Framebuffer>, frame: &Framebuffer) -> Option<(i32, i32, i32, i32)> {
+    let sent = match sent {
+        Some(sent) => sent,
+        None => return Some((0, 0, WIDTH as i32 - 1, HEIGHT as i32 - 1)),
+    };
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for y in 0..HEIGHT as i32 {
+        for x in 0..WIDTH as i32 {
+            if sent.get(x, y) == frame.get(x, y) {
+                continue;
+            }
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+    bounds
+}
+
+impl<D: Display> Display for Renderer<D> {
+    fn clear(&mut self) -> Result<(), MenuError> {
+        self.frame.clear()
+    }
+
+    fn write(&mut self, x: i32, y: i32, text: &str, font: &str) -> Result<(), MenuError> {
+        self.frame.write(x, y, text, font)
+    }
+
+    fn draw(
+        &mut self,
+        bytes: &[u8],
+        width: u32,
+        height: u32,
+        x: i32,
+        y: i32,
+    ) -> Result<(), MenuError> {
+        self.frame.draw(bytes, width, height, x, y)
+    }
+
+    fn flush(&mut self) -> Result<(), MenuError> {
+        let now = Renderer::<D>::now();
+        self.send(Transform::at(&self.burn_in, now), now)
+    }
+
+    /// Everything is sent again once the display is turned on, in case it
+    /// lost its contents while off.
+    fn power(&mut self, on: bool) -> Result<(), MenuError> {
+        if on {
+            self.sent = None;
+        }
+        self.display.power(on)
+    }
+
+    /// Sends the whole frame again when its offset or inversion is due to
+    /// change, or `FULL_INTERVAL` seconds after it was last sent.
+    fn tick(&mut self) -> Result<(), MenuError> {
+        let now = Renderer::<D>::now();
+        let transform = Transform::at(&self.burn_in, now);
+        let moved = self.transform.map_or(false, |shown| shown != transform);
+        if self.transform.is_none() || (!moved && now < self.full + FULL_INTERVAL) {
+            return Ok(());
+        }
+        self.sent = None;
+        self.send(transform, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Recording;
+
+    fn still() -> Transform {
+        let burn_in = BurnIn {
+            shift: 0,
+            shift_interval: 60,
+            invert_interval: 0,
+        };
+        Transform::at(&burn_in, 0)
+    }
+
+    #[test]
+    fn everything_changed_when_nothing_sent() {
+        let frame = Framebuffer::new();
+        assert_eq!(changed(None, &frame), Some((0, 0, 127, 63)));
+    }
+
+    #[test]
+    fn nothing_changed_for_same_frame() {
+        let mut frame = Framebuffer::new();
+        frame.set(3, 4, true);
+        assert_eq!(changed(Some(&frame.clone()), &frame), None);
+    }
+
+    #[test]
+    fn changed_pixels_are_bounded() {
+        let sent = Framebuffer::new();
+        let mut frame = Framebuffer::new();
+        frame.set(10, 20, true);
+        assert_eq!(changed(Some(&sent), &frame), Some((10, 20, 10, 20)));
+        frame.set(40, 5, true);
+        assert_eq!(changed(Some(&sent), &frame), Some((10, 5, 40, 20)));
+        assert_eq!(changed(Some(&frame), &sent), Some((10, 5, 40, 20)));
+    }
+
+    #[test]
+    fn whole_frame_is_sent_first_and_on_power_on() {
+        let mut renderer = Renderer::new(Recording::default(), BurnIn::default());
+        renderer.send(still(), 1000).unwrap();
+        renderer.send(still(), 1001).unwrap();
+        renderer.frame.set(5, 6, true);
+        renderer.send(still(), 1002).unwrap();
+        assert_eq!(
+            renderer.display.calls(),
+            vec!["draw 128x64 at 0,0", "flush", "draw 1x1 at 5,6", "flush"]
+        );
+        assert_eq!(renderer.full, 1000);
+        renderer.power(true).unwrap();
+        renderer.send(still(), 1003).unwrap();
+        let calls = renderer.display.calls();
+        assert_eq!(calls[calls.len() - 2..], ["draw 128x64 at 0,0", "flush"]);
+        assert_eq!(renderer.full, 1003);
+    }
+}
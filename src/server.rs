@@ -0,0 +1,116 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crossbeam_channel::Sender;
+use jsonrpc_http_server::jsonrpc_core::{Error, IoHandler, Params, Value};
+use jsonrpc_http_server::{Server, ServerBuilder};
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::error::MenuError;
+use crate::menu::Target;
+use crate::state_machine::{Event, Input, State};
+
+/// The screens which may be opened with `goto`.
+const SCREENS: [&str; 7] = ["logo", "network", "qr", "saved", "scan", "stats", "storage"];
+
+#[derive(Debug, Deserialize)]
+struct SendEvent {
+    event: Event,
+}
+
+/// Starts a JSON-RPC HTTP server for controlling and inspecting the menu.
+///
+/// Injected events and state jumps are passed to `state_changer` over the
+/// same channel as button codes from `peach_buttons`. The server runs until
+/// the returned `Server` is dropped.
+///
+/// # Methods
+///
+/// * `get_state` - Returns the current state and selected index.
+/// * `send_event` - Injects an event, e.g. `{"event": "down"}`.
+/// * `goto` - Jumps to a menu or screen, e.g. `{"menu": "home"}` or
+///   `{"screen": "stats"}`.
+/// * `list_states` - Returns the screens and menus accepted by `goto`.
+///
+/// # Arguments
+///
+/// * `addr` - The address and port to listen on.
+/// * `s` - A `crossbeam_channel::Sender` for passing input to the state machine.
+/// * `current` - The current state, as published by `state_changer`.
+/// * `menus` - The identifiers of the configured menus.
+///
+pub fn start(
+    addr: &str,
+    s: Sender<Input>,
+    current: Arc<Mutex<State>>,
+    menus: Vec<String>,
+) -> Result<Server, MenuError> {
+    let mut io = IoHandler::default();
+
+    io.add_method("get_state", move |_| {
+        debug!("Received get_state request.");
+        let state = current.lock().map_err(|_| Error::internal_error())?;
+        let menu = match *state {
+            State::Menu(ref id, _) => Some(id.to_string()),
+            _ => None,
+        };
+        Ok(json!({
+            "state": state.name(),
+            "menu": menu,
+            "selected": state.selected(),
+        }))
+    });
+
+    let event_sender = s.clone();
+    io.add_method("send_event", move |params: Params| {
+        let params: SendEvent = params.parse()?;
+        debug!("Received send_event request: {:?}.", params.event);
+        if params.event == Event::Unknown {
+            return Err(Error::invalid_params("unknown event"));
+        }
+        event_sender
+            .send(Input::Event(params.event))
+            .map_err(|_| Error::internal_error())?;
+        Ok(Value::String("success".to_string()))
+    });
+
+    let goto_menus = menus.clone();
+    io.add_method("goto", move |params: Params| {
+        let target: Target = params.parse()?;
+        debug!("Received goto request: {:?}.", target);
+        match target {
+            Target::Action(_) => {
+                return Err(Error::invalid_params("actions cannot be run with goto"));
+            }
+            Target::Menu(ref id) if !goto_menus.contains(id) => {
+                return Err(Error::invalid_params(format!("unknown menu: {}", id)));
+            }
+            _ => (),
+        }
+        let state = match target.state() {
+            Some(state) => state,
+            None => return Err(Error::invalid_params("actions cannot be run with goto")),
+        };
+        s.send(Input::Goto(state))
+            .map_err(|_| Error::internal_error())?;
+        Ok(Value::String("success".to_string()))
+    });
+
+    io.add_method("list_states", move |_| {
+        debug!("Received list_states request.");
+        Ok(json!({
+            "screens": SCREENS,
+            "menus": menus,
+        }))
+    });
+
+    let addr: SocketAddr = addr
+        .parse()
+        .map_err(|e| MenuError::Config(format!("invalid server address '{}': {}", addr, e)))?;
+    let server = ServerBuilder::new(io).start_http(&addr)?;
+    info!("Listening for JSON-RPC requests on {}.", addr);
+
+    Ok(server)
+}
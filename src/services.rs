@@ -0,0 +1,494 @@
+use std::cell::{Cell, RefCell};
+use std::time::Instant;
+use std::{fs, io, process};
+
+use log::info;
+
+use peach_lib::network_client;
+use peach_lib::stats_client;
+
+use crate::error::MenuError;
+use crate::structs::{
+    CpuStatPercentages, DiskUsage, LoadAverage, MemStat, Networks, Scan, Traffic,
+};
+
+/// The network and system operations used by the states.
+///
+/// The methods mirror the `peach-network` and `peach-stats` JSON-RPC calls
+/// made through `peach_lib`, along with the system commands for rebooting
+/// and shutting down the device.
+pub trait Services {
+    /// Returns the operational state of `iface` (for example `up` or `down`).
+    fn state(&self, iface: &str) -> Result<String, MenuError>;
+
+    /// Returns the IP address of `iface`.
+    fn ip(&self, iface: &str) -> Result<String, MenuError>;
+
+    /// Returns the SSID of the network `iface` is connected to.
+    fn ssid(&self, iface: &str) -> Result<String, MenuError>;
+
+    /// Returns the signal strength of the connection on `iface` in dBm.
+    fn rssi(&self, iface: &str) -> Result<String, MenuError>;
+
+    /// Returns the total bytes received and transmitted on `iface`.
+    fn traffic(&self, iface: &str) -> Result<Traffic, MenuError>;
+
+    /// Returns the wireless networks visible to `iface`.
+    fn available_networks(&self, iface: &str) -> Result<Vec<Scan>, MenuError>;
+
+    /// Returns the SSIDs of the networks with saved credentials.
+    fn saved_networks(&self) -> Result<Vec<String>, MenuError>;
+
+    /// Saves the credentials for the network `ssid`.
+    fn add(&self, ssid: &str, pass: &str) -> Result<(), MenuError>;
+
+    /// Connects `iface` to the saved network `ssid`.
+    fn connect(&self, ssid: &str, iface: &str) -> Result<(), MenuError>;
+
+    /// Removes the saved credentials for `ssid`.
+    fn forget(&self, ssid: &str, iface: &str) -> Result<(), MenuError>;
+
+    /// Returns the priority of the saved network `ssid`. Networks with a
+    /// higher priority are preferred when more than one is in range.
+    fn priority(&self, ssid: &str, iface: &str) -> Result<i32, MenuError>;
+
+    /// Sets the priority of the saved network `ssid`.
+    fn set_priority(&self, ssid: &str, iface: &str, priority: i32) -> Result<(), MenuError>;
+
+    /// Switches the wireless interface to client mode.
+    fn activate_client(&self) -> Result<(), MenuError>;
+
+    /// Switches the wireless interface to access point mode.
+    fn activate_ap(&self) -> Result<(), MenuError>;
+
+    /// Returns the contents of the `hostapd` configuration file.
+    fn hostapd_config(&self) -> Result<String, MenuError>;
+
+    /// Returns the number of clients connected to the access point on
+    /// `iface`.
+    fn ap_clients(&self, iface: &str) -> Result<usize, MenuError>;
+
+    fn cpu_stats_percent(&self) -> Result<CpuStatPercentages, MenuError>;
+
+    fn mem_stats(&self) -> Result<MemStat, MenuError>;
+
+    fn load_average(&self) -> Result<LoadAverage, MenuError>;
+
+    /// Returns the usage of each mounted filesystem.
+    fn disk_usage(&self) -> Result<Vec<DiskUsage>, MenuError>;
+
+    /// Returns the system uptime in minutes.
+    fn uptime(&self) -> Result<String, MenuError>;
+
+    fn reboot(&self) -> Result<(), MenuError>;
+
+    fn shutdown(&self) -> Result<(), MenuError>;
+}
+
+/// The configuration file of the access point.
+const HOSTAPD_CONF: &str = "/etc/hostapd/hostapd.conf";
+
+/// Makes JSON-RPC calls to the PeachCloud microservices and runs system
+/// commands on the device.
+#[derive(Debug, Default)]
+pub struct Peach;
+
+impl Services for Peach {
+    fn state(&self, iface: &str) -> Result<String, MenuError> {
+        Ok(network_client::state(iface)?)
+    }
+
+    fn ip(&self, iface: &str) -> Result<String, MenuError> {
+        Ok(network_client::ip(iface)?)
+    }
+
+    fn ssid(&self, iface: &str) -> Result<String, MenuError> {
+        Ok(network_client::ssid(iface)?)
+    }
+
+    fn rssi(&self, iface: &str) -> Result<String, MenuError> {
+        Ok(network_client::rssi(iface)?)
+    }
+
+    fn traffic(&self, iface: &str) -> Result<Traffic, MenuError> {
+        let traffic = network_client::traffic(iface)?;
+        Ok(Traffic {
+            received: traffic.received,
+            transmitted: traffic.transmitted,
+        })
+    }
+
+    fn available_networks(&self, iface: &str) -> Result<Vec<Scan>, MenuError> {
+        let networks = network_client::available_networks(iface)?;
+        Ok(serde_json::from_str(&networks)?)
+    }
+
+    fn saved_networks(&self) -> Result<Vec<String>, MenuError> {
+        let networks = network_client::saved_networks()?;
+        let networks: Vec<Networks> = serde_json::from_str(&networks)?;
+        Ok(networks.into_iter().map(|network| network.ssid).collect())
+    }
+
+    fn add(&self, ssid: &str, pass: &str) -> Result<(), MenuError> {
+        network_client::add(ssid, pass)?;
+        Ok(())
+    }
+
+    fn connect(&self, ssid: &str, iface: &str) -> Result<(), MenuError> {
+        let id = network_client::id(iface, ssid)?;
+        network_client::connect(&id, iface)?;
+        Ok(())
+    }
+
+    fn forget(&self, ssid: &str, iface: &str) -> Result<(), MenuError> {
+        network_client::forget(iface, ssid)?;
+        Ok(())
+    }
+
+    fn priority(&self, ssid: &str, iface: &str) -> Result<i32, MenuError> {
+        let id = network_client::id(iface, ssid)?;
+        let priority = wpa_cli(iface, &["get_network", &id, "priority"])?;
+        Ok(priority.parse().unwrap_or(0))
+    }
+
+    fn set_priority(&self, ssid: &str, iface: &str, priority: i32) -> Result<(), MenuError> {
+        let id = network_client::id(iface, ssid)?;
+        wpa_cli(
+            iface,
+            &["set_network", &id, "priority", &priority.to_string()],
+        )?;
+        wpa_cli(iface, &["save_config"])?;
+        Ok(())
+    }
+
+    fn activate_client(&self) -> Result<(), MenuError> {
+        network_client::activate_client()?;
+        Ok(())
+    }
+
+    fn activate_ap(&self) -> Result<(), MenuError> {
+        network_client::activate_ap()?;
+        Ok(())
+    }
+
+    fn hostapd_config(&self) -> Result<String, MenuError> {
+        Ok(fs::read_to_string(HOSTAPD_CONF)?)
+    }
+
+    /// `peach-network` does not report the clients of the access point, so
+    /// they are counted from the stations known to the interface.
+    fn ap_clients(&self, iface: &str) -> Result<usize, MenuError> {
+        let output = process::Command::new("/sbin/iw")
+            .args(["dev", iface, "station", "dump"])
+            .output()?;
+        if !output.status.success() {
+            return Err(MenuError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                format!("iw dev {} station dump failed", iface),
+            )));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout
+            .lines()
+            .filter(|line| line.starts_with("Station "))
+            .count())
+    }
+
+    fn cpu_stats_percent(&self) -> Result<CpuStatPercentages, MenuError> {
+        let cpu = stats_client::cpu_stats_percent()?;
+        Ok(CpuStatPercentages {
+            user: cpu.user,
+            system: cpu.system,
+            idle: cpu.idle,
+            nice: cpu.nice,
+        })
+    }
+
+    fn mem_stats(&self) -> Result<MemStat, MenuError> {
+        let mem = stats_client::mem_stats()?;
+        Ok(MemStat {
+            total: mem.total,
+            free: mem.free,
+            used: mem.used,
+        })
+    }
+
+    fn load_average(&self) -> Result<LoadAverage, MenuError> {
+        let load = stats_client::load_average()?;
+        Ok(LoadAverage {
+            one: load.one,
+            five: load.five,
+            fifteen: load.fifteen,
+        })
+    }
+
+    fn disk_usage(&self) -> Result<Vec<DiskUsage>, MenuError> {
+        let disks = stats_client::disk_usage()?;
+        Ok(serde_json::from_str(&disks)?)
+    }
+
+    fn uptime(&self) -> Result<String, MenuError> {
+        Ok(stats_client::uptime()?.to_string())
+    }
+
+    fn reboot(&self) -> Result<(), MenuError> {
+        info!("Rebooting device");
+        process::Command::new("sudo")
+            .arg("/sbin/shutdown")
+            .arg("-r")
+            .arg("now")
+            .output()?;
+        Ok(())
+    }
+
+    fn shutdown(&self) -> Result<(), MenuError> {
+        info!("Shutting down device");
+        process::Command::new("sudo")
+            .arg("/sbin/shutdown")
+            .arg("now")
+            .output()?;
+        Ok(())
+    }
+}
+
+/// Runs a `wpa_cli` command for `iface` and returns its trimmed output.
+///
+/// `peach-network` does not expose network priorities, so they are read and
+/// written directly.
+fn wpa_cli(iface: &str, args: &[&str]) -> Result<String, MenuError> {
+    let output = process::Command::new("sudo")
+        .arg("/sbin/wpa_cli")
+        .arg("-i")
+        .arg(iface)
+        .args(args)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || stdout == "FAIL" {
+        return Err(MenuError::Io(io::Error::new(
+            io::ErrorKind::Other,
+            format!("wpa_cli {} failed: {}", args.join(" "), stdout),
+        )));
+    }
+    Ok(stdout)
+}
+
+/// Returns plausible, slowly changing data without contacting any
+/// PeachCloud microservices. Used for developing off-device.
+#[derive(Debug)]
+pub struct Simulated {
+    started: Instant,
+    ap_mode: Cell<bool>,
+    /// SSIDs and priorities of the saved networks.
+    saved: RefCell<Vec<(String, i32)>>,
+}
+
+impl Simulated {
+    pub fn new() -> Simulated {
+        Simulated {
+            started: Instant::now(),
+            ap_mode: Cell::new(false),
+            saved: RefCell::new(vec![
+                ("PeachNet".to_string(), 2),
+                ("Cafe Guest".to_string(), 0),
+                ("Office".to_string(), 1),
+            ]),
+        }
+    }
+
+    fn secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+}
+
+impl Default for Simulated {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Services for Simulated {
+    fn state(&self, iface: &str) -> Result<String, MenuError> {
+        let state = match iface {
+            "wlan0" if !self.ap_mode.get() => "up",
+            "ap0" if self.ap_mode.get() => "up",
+            // no cable plugged in
+            "eth0" => "lowerlayerdown",
+            "usb0" => "unknown",
+            _ => "down",
+        };
+        Ok(state.to_string())
+    }
+
+    fn ip(&self, iface: &str) -> Result<String, MenuError> {
+        match iface {
+            "wlan0" if !self.ap_mode.get() => Ok("192.168.1.23".to_string()),
+            "ap0" if self.ap_mode.get() => Ok("11.11.11.10".to_string()),
+            "usb0" => Ok("10.42.0.1".to_string()),
+            _ => Err(MenuError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                format!("no address on {}", iface),
+            ))),
+        }
+    }
+
+    fn ssid(&self, _iface: &str) -> Result<String, MenuError> {
+        Ok("PeachNet".to_string())
+    }
+
+    fn rssi(&self, _iface: &str) -> Result<String, MenuError> {
+        Ok(format!("-{}", 50 + self.secs() % 7))
+    }
+
+    fn traffic(&self, _iface: &str) -> Result<Traffic, MenuError> {
+        let secs = self.secs();
+        Ok(Traffic {
+            received: 48_000_000 + secs * 180_000,
+            transmitted: 6_500_000 + secs * 25_000,
+        })
+    }
+
+    fn available_networks(&self, _iface: &str) -> Result<Vec<Scan>, MenuError> {
+        let networks = [
+            ("PeachNet", "[WPA2-PSK-CCMP][ESS]", -52),
+            ("Cafe Guest", "[ESS]", -71),
+            ("Neighbour 5G", "[WPA2-PSK-CCMP][WPS][ESS]", -80),
+            ("PeachNet", "[WPA2-PSK-CCMP][ESS]", -64),
+            ("Library", "[WPA2-EAP-CCMP][ESS]", -67),
+            ("", "[WPA2-PSK-CCMP][ESS]", -58),
+            ("Workshop", "[WPA2-PSK-CCMP][ESS]", -75),
+            ("FreeWifi", "[ESS]", -88),
+            ("Studio", "[WEP][ESS]", -84),
+        ];
+        Ok(networks
+            .iter()
+            .map(|&(ssid, protocol, signal)| Scan {
+                frequency: "2437".to_string(),
+                protocol: protocol.to_string(),
+                signal_level: (signal - (self.secs() % 3) as i32).to_string(),
+                ssid: ssid.to_string(),
+            })
+            .collect())
+    }
+
+    fn saved_networks(&self) -> Result<Vec<String>, MenuError> {
+        let saved = self.saved.borrow();
+        Ok(saved.iter().map(|(ssid, _)| ssid.to_string()).collect())
+    }
+
+    fn add(&self, ssid: &str, _pass: &str) -> Result<(), MenuError> {
+        info!("Simulated saving {}", ssid);
+        let mut saved = self.saved.borrow_mut();
+        if !saved.iter().any(|(saved, _)| saved == ssid) {
+            saved.push((ssid.to_string(), 0));
+        }
+        Ok(())
+    }
+
+    fn connect(&self, ssid: &str, _iface: &str) -> Result<(), MenuError> {
+        info!("Simulated connection to {}", ssid);
+        self.ap_mode.set(false);
+        Ok(())
+    }
+
+    fn forget(&self, ssid: &str, _iface: &str) -> Result<(), MenuError> {
+        info!("Simulated forgetting {}", ssid);
+        self.saved.borrow_mut().retain(|(saved, _)| saved != ssid);
+        Ok(())
+    }
+
+    fn priority(&self, ssid: &str, _iface: &str) -> Result<i32, MenuError> {
+        let saved = self.saved.borrow();
+        Ok(saved
+            .iter()
+            .find(|(saved, _)| saved == ssid)
+            .map_or(0, |&(_, priority)| priority))
+    }
+
+    fn set_priority(&self, ssid: &str, _iface: &str, priority: i32) -> Result<(), MenuError> {
+        let mut saved = self.saved.borrow_mut();
+        if let Some(network) = saved.iter_mut().find(|(saved, _)| saved == ssid) {
+            network.1 = priority;
+        }
+        Ok(())
+    }
+
+    fn activate_client(&self) -> Result<(), MenuError> {
+        self.ap_mode.set(false);
+        Ok(())
+    }
+
+    fn activate_ap(&self) -> Result<(), MenuError> {
+        self.ap_mode.set(true);
+        Ok(())
+    }
+
+    fn hostapd_config(&self) -> Result<String, MenuError> {
+        Ok("interface=ap0\n\
+            ssid=peach\n\
+            channel=6\n\
+            wpa=2\n\
+            wpa_passphrase=cloudpeach\n"
+            .to_string())
+    }
+
+    fn ap_clients(&self, _iface: &str) -> Result<usize, MenuError> {
+        Ok(if self.ap_mode.get() { 2 } else { 0 })
+    }
+
+    fn cpu_stats_percent(&self) -> Result<CpuStatPercentages, MenuError> {
+        let user = 5.0 + (self.secs() % 20) as f32;
+        let system = 3.0;
+        Ok(CpuStatPercentages {
+            user,
+            system,
+            idle: 100.0 - user - system,
+            nice: 0.0,
+        })
+    }
+
+    fn mem_stats(&self) -> Result<MemStat, MenuError> {
+        Ok(MemStat {
+            total: 949_392,
+            free: 612_880,
+            used: 336_512,
+        })
+    }
+
+    fn load_average(&self) -> Result<LoadAverage, MenuError> {
+        Ok(LoadAverage {
+            one: 0.12,
+            five: 0.08,
+            fifteen: 0.05,
+        })
+    }
+
+    fn disk_usage(&self) -> Result<Vec<DiskUsage>, MenuError> {
+        let disk = |filesystem: &str, mountpoint: &str, blocks: u64, used: u64| DiskUsage {
+            filesystem: Some(filesystem.to_string()),
+            one_k_blocks: blocks,
+            one_k_blocks_used: used,
+            one_k_blocks_free: blocks - used,
+            used_percentage: (used * 100 / blocks) as u32,
+            mountpoint: mountpoint.to_string(),
+        };
+        Ok(vec![
+            disk("/dev/mmcblk0p2", "/", 15_023_184, 9_465_604),
+            disk("/dev/mmcblk0p1", "/boot/firmware", 306_128, 63_544),
+            disk("/dev/sda1", "/mnt/data", 122_030_736, 113_488_584),
+        ])
+    }
+
+    fn uptime(&self) -> Result<String, MenuError> {
+        Ok((self.secs() / 60).to_string())
+    }
+
+    fn reboot(&self) -> Result<(), MenuError> {
+        info!("Simulated reboot");
+        Ok(())
+    }
+
+    fn shutdown(&self) -> Result<(), MenuError> {
+        info!("Simulated shutdown");
+        Ok(())
+    }
+}
@@ -1,14 +1,70 @@
-use std::{process, thread};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Local};
 use crossbeam_channel::*;
-use log::{error, info, warn};
-
-use peach_lib::error::PeachError;
-use peach_lib::oled_client;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 
+use crate::config::Config;
+use crate::display::Display;
+use crate::error::MenuError;
+use crate::gestures::Recognizer;
+use crate::keyboard::{Keyboard, Outcome, Secret};
+use crate::menu::{
+    navigate, selected_action, Action, Command, Screen, Target, MAINTENANCE_MENU, NETWORK_CONF_MENU,
+};
+use crate::monitor::Monitor;
+use crate::network::Role;
+use crate::power::{DisplayPower, Phase};
+use crate::services::Services;
 use crate::states::*;
+use crate::structs::DiskUsage;
+use crate::wifi::{
+    self, AccessPoint, SavedNetwork, MAX_PASSPHRASE_LEN, MIN_PASSPHRASE_LEN, WLAN_IFACE,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+/// The physical buttons.
+pub enum Button {
+    Center,
+    Left,
+    Right,
+    Down,
+    Up,
+    A,
+    B,
+}
+
+impl Button {
+    /// Every button, each of which must be mapped to a button code.
+    pub const ALL: [Button; 7] = [
+        Button::Center,
+        Button::Left,
+        Button::Right,
+        Button::Down,
+        Button::Up,
+        Button::A,
+        Button::B,
+    ];
 
-#[derive(Debug, Clone, Copy)]
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::Center => "Center",
+            Button::Left => "Left",
+            Button::Right => "Right",
+            Button::Down => "Down",
+            Button::Up => "Up",
+            Button::A => "A",
+            Button::B => "B",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 /// The button press events.
 pub enum Event {
     Center,
@@ -18,21 +74,85 @@ pub enum Event {
     Up,
     A,
     B,
+    /// A second press of a button within the double press time.
+    Double(Button),
+    /// Two buttons pressed together.
+    Chord(Button, Button),
     Unknown,
 }
 
-#[derive(Debug, PartialEq)]
+impl From<Button> for Event {
+    fn from(button: Button) -> Event {
+        match button {
+            Button::Center => Event::Center,
+            Button::Left => Event::Left,
+            Button::Right => Event::Right,
+            Button::Down => Event::Down,
+            Button::Up => Event::Up,
+            Button::A => Event::A,
+            Button::B => Event::B,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The messages received by the state machine thread.
+pub enum Input {
+    /// A button code received from `peach_buttons`.
+    Button(u8),
+    /// An event injected through the JSON-RPC server.
+    Event(Event),
+    /// A jump to a state, requested through the JSON-RPC server.
+    Goto(State),
+    /// The connection to `peach_buttons` has been established.
+    ButtonsOnline,
+    /// The connection to `peach_buttons` has been lost.
+    ButtonsOffline,
+    /// A periodic timer event, used to refresh live screens.
+    Tick,
+    /// A request to stop the state machine.
+    Shutdown,
+}
+
+/// The pairs of buttons which form a chord. Only used when the maintenance
+/// menu is declared, so that A and B are not otherwise held back.
+const CHORDS: &[(Button, Button)] = &[(Button::A, Button::B)];
+
+/// The interval between `Input::Tick` events.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The configuration, display and services used by the state machine.
+pub struct Context {
+    pub config: Config,
+    pub display: Box<dyn Display + Send>,
+    pub services: Box<dyn Services + Send>,
+    /// System and network activity, sampled in the background.
+    pub monitor: Monitor,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 /// The states of the state machine.
 pub enum State {
-    Home(u8),
+    Confirm(Box<State>, u8, Box<State>),
+    Connect(String),
+    Forget(String),
+    Join(String, Secret),
+    Keyboard(String, Keyboard),
     Logo,
-    Network,
-    NetworkConf(u8),
+    Menu(String, usize),
+    Network(usize),
     NetworkMode(u8),
-    OledPower(u8),
+    Open(Vec<AccessPoint>, usize),
+    Passphrase(usize, u8),
+    Prioritise(Vec<SavedNetwork>, usize),
+    Qr(u8),
     Reboot,
+    Saved(Vec<SavedNetwork>, usize),
+    SavedOptions(Vec<SavedNetwork>, usize, usize),
+    Scan(Vec<AccessPoint>, usize),
     Shutdown,
-    Stats,
+    Stats(u8),
+    Storage(Vec<DiskUsage>, usize),
 }
 
 /// Initializes the state machine, listens for button events and drives
@@ -40,206 +160,1055 @@ pub enum State {
 ///
 /// # Arguments
 ///
-/// * `r` - An unbounded `crossbeam_channel::Receiver` for unsigned 8 byte int.
+/// * `r` - An unbounded `crossbeam_channel::Receiver` for `Input` messages.
+/// * `ctx` - The configuration, display and services used to run states.
+/// * `current` - Updated with the current state after every state change.
+///
+/// The current state is retained while `peach_buttons` is offline. An offline
+/// indicator is displayed until the connection is re-established, at which
+/// point the current state is rendered again. Neither is drawn while the
+/// display is idle, and a state which performs an action is replaced by the
+/// screen it returns to rather than entered again.
+///
+/// A `Tick` is generated every second alongside the received input. States
+/// which show live data are refreshed whenever the wall clock crosses a
+/// multiple of their refresh interval. Activity is sampled at the Stats
+/// refresh interval, whatever the current state, so that its history is
+/// available as soon as the Stats screen is opened. Sampling pauses while the
+/// display is off.
+///
+/// When no button has been pressed for the configured idle timeouts, the
+/// display shows a clock screensaver and is then powered off. It is also
+/// powered off by the `DisplayOff` menu action, which leaves the current
+/// state unchanged. The next press only wakes the display and redraws the current
+/// state.
 ///
-pub fn state_changer(r: Receiver<u8>) {
+/// The thread returns once `Input::Shutdown` is received or all senders for
+/// `r` have been dropped.
+///
+pub fn state_changer(
+    r: Receiver<Input>,
+    mut ctx: Context,
+    current: Arc<Mutex<State>>,
+) -> thread::JoinHandle<()> {
     thread::spawn(move || {
         info!("Initializing the state machine.");
         let mut state = State::Logo;
-        match state.run() {
+        match state.run(&mut ctx) {
             Ok(_) => (),
             Err(e) => warn!("State machine error: {:?}", e),
         };
+        publish(&current, &state);
+
+        let mut online = true;
+        let mut last_run = Local::now();
+        let mut last_sample = last_run;
+        let mut power = DisplayPower::new(last_run);
+        ctx.monitor.sample(ctx.services.as_ref());
+        let ticker = tick(TICK_INTERVAL);
+        let chords = match ctx.config.menus.contains_key(MAINTENANCE_MENU) {
+            true => CHORDS,
+            false => &[],
+        };
+        let mut gestures = Recognizer::new(ctx.config.gestures.clone(), chords);
 
         loop {
-            let button_code = r.recv().unwrap_or_else(|err| {
-                error!("Problem receiving button code from server: {}", err);
-                process::exit(1);
-            });
-            let event = match button_code {
-                0 => Event::Center,
-                1 => Event::Left,
-                2 => Event::Right,
-                3 => Event::Up,
-                4 => Event::Down,
-                5 => Event::A,
-                6 => Event::B,
-                _ => Event::Unknown,
+            let timeout = match gestures.deadline() {
+                Some(deadline) => after(deadline.saturating_duration_since(Instant::now())),
+                None => never(),
             };
-            state = state.next(event);
-            match state.run() {
-                Ok(_) => (),
-                Err(e) => warn!("State machine error: {:?}", e),
+            let input = select! {
+                recv(r) -> input => input.ok(),
+                recv(ticker) -> _ => Some(Input::Tick),
+                recv(timeout) -> _ => {
+                    for event in gestures.expire(Instant::now()) {
+                        state = handle(&mut ctx, state, event, &mut power);
+                        last_run = Local::now();
+                        publish(&current, &state);
+                    }
+                    continue;
+                }
             };
+            let input = match input {
+                Some(input) => input,
+                None => {
+                    info!("Input channel closed. Stopping state machine.");
+                    break;
+                }
+            };
+            let events = match input {
+                Input::Button(code) => match ctx.config.buttons.decode(code) {
+                    Some(button) => gestures.press(button, Instant::now()),
+                    None => vec![Event::Unknown],
+                },
+                Input::Event(event) => vec![event],
+                Input::Goto(next) => {
+                    if power.press(Local::now()) == Phase::Off {
+                        if let Err(e) = ctx.display.power(true) {
+                            warn!("State machine error: {:?}", e);
+                        }
+                    }
+                    state = next.load(ctx.services.as_ref());
+                    match state.run(&mut ctx) {
+                        Ok(_) => (),
+                        Err(e) => warn!("State machine error: {:?}", e),
+                    };
+                    last_run = Local::now();
+                    publish(&current, &state);
+                    continue;
+                }
+                Input::Tick => {
+                    let now = Local::now();
+                    let interval = ctx.config.refresh.stats.max(1) as i64;
+                    // nothing can be shown while the display is off
+                    if power.phase() != Phase::Off
+                        && now.timestamp() / interval != last_sample.timestamp() / interval
+                    {
+                        ctx.monitor.sample(ctx.services.as_ref());
+                        last_sample = now;
+                    }
+                    if !online {
+                        continue;
+                    }
+                    if let Some(phase) = power.tick(&ctx.config.idle, now) {
+                        match idle_display(&mut ctx, phase, now) {
+                            Ok(_) => (),
+                            Err(e) => warn!("State machine error: {:?}", e),
+                        };
+                        last_run = now;
+                    }
+                    match power.phase() {
+                        Phase::Awake => (),
+                        Phase::Screensaver => {
+                            // move the clock on the minute
+                            if now.timestamp() / 60 != last_run.timestamp() / 60 {
+                                match state_screensaver(ctx.display.as_mut(), now) {
+                                    Ok(_) => (),
+                                    Err(e) => warn!("State machine error: {:?}", e),
+                                };
+                                last_run = now;
+                            }
+                            continue;
+                        }
+                        Phase::Off => continue,
+                    }
+                    if let Err(e) = ctx.display.tick() {
+                        warn!("State machine error: {:?}", e);
+                    }
+                    if let Some(next) = state.tick() {
+                        state = next.load(ctx.services.as_ref());
+                        match state.run(&mut ctx) {
+                            Ok(_) => (),
+                            Err(e) => warn!("State machine error: {:?}", e),
+                        };
+                        last_run = now;
+                        publish(&current, &state);
+                    } else if state.refresh_due(&ctx.config, last_run, now) {
+                        match state.refresh(&mut ctx) {
+                            Ok(_) => (),
+                            Err(e) => warn!("State machine error: {:?}", e),
+                        };
+                        last_run = now;
+                    }
+                    continue;
+                }
+                Input::Shutdown => {
+                    info!("Stopping state machine.");
+                    break;
+                }
+                Input::ButtonsOnline => {
+                    if !online {
+                        info!("Connection to peach_buttons restored.");
+                        online = true;
+                        // entering an action state again would repeat its action
+                        if state.performs_action() {
+                            state = state.settled(&ctx.config).load(ctx.services.as_ref());
+                            publish(&current, &state);
+                        }
+                        // an idle display is redrawn when it wakes
+                        if power.phase() == Phase::Awake {
+                            match state.run(&mut ctx) {
+                                Ok(_) => (),
+                                Err(e) => warn!("State machine error: {:?}", e),
+                            };
+                            last_run = Local::now();
+                        }
+                    }
+                    continue;
+                }
+                Input::ButtonsOffline => {
+                    if online {
+                        warn!("Connection to peach_buttons lost.");
+                        online = false;
+                        if power.phase() == Phase::Awake {
+                            match state_buttons_offline(ctx.display.as_mut()) {
+                                Ok(_) => (),
+                                Err(e) => warn!("State machine error: {:?}", e),
+                            };
+                        }
+                    }
+                    continue;
+                }
+            };
+            for event in events {
+                state = handle(&mut ctx, state, event, &mut power);
+                last_run = Local::now();
+                publish(&current, &state);
+            }
         }
-    });
+    })
 }
 
-// 0 - Home
-//   1 - Networking
-//   2 - System Stats
-//   3 - Display Off
-//   4 - Reboot
-//   5 - Shutdown
-// 0 - NetworkConf
-//   1 - Client Mode
-//   2 - Access Point
+/// Moves to the state following `event` and runs it.
+///
+/// If the display is idle, the event only wakes it and the current state is
+/// run again, or the screen it returns to if it performs an action. If the
+/// event selects turning the display off, the current state is kept.
+fn handle(ctx: &mut Context, state: State, event: Event, power: &mut DisplayPower) -> State {
+    let phase = power.press(Local::now());
+    if phase != Phase::Awake {
+        info!("Waking the display.");
+        if phase == Phase::Off {
+            if let Err(e) = ctx.display.power(true) {
+                warn!("State machine error: {:?}", e);
+            }
+        }
+        // entering an action state again would repeat its action
+        let state = state.settled(&ctx.config).load(ctx.services.as_ref());
+        match state.run(ctx) {
+            Ok(_) => (),
+            Err(e) => warn!("State machine error: {:?}", e),
+        };
+        return state;
+    }
+    if let Some(Action::DisplayOff) = state.action(event, &ctx.config) {
+        info!("Turning the display off.");
+        power.off();
+        if let Err(e) = ctx.display.power(false) {
+            warn!("State machine error: {:?}", e);
+        }
+        return state;
+    }
+    // an action state shows the result of its action, which is not run
+    // again by presses which do not leave it
+    let acted = match state.performs_action() {
+        true => Some(state.clone()),
+        false => None,
+    };
+    let state = state.next(event, &ctx.config);
+    if acted.as_ref() == Some(&state) {
+        return state;
+    }
+    let state = state.load(ctx.services.as_ref());
+    match state.run(ctx) {
+        Ok(_) => (),
+        Err(e) => warn!("State machine error: {:?}", e),
+    };
+    state
+}
+
+/// Shows the screensaver or turns the display off when the idle phase
+/// changes.
+fn idle_display(ctx: &mut Context, phase: Phase, now: DateTime<Local>) -> Result<(), MenuError> {
+    match phase {
+        Phase::Awake => Ok(()),
+        Phase::Screensaver => {
+            info!("Showing the screensaver.");
+            state_screensaver(ctx.display.as_mut(), now)
+        }
+        Phase::Off => {
+            info!("Turning the display off.");
+            ctx.display.power(false)
+        }
+    }
+}
+
+/// Returns the network configuration menu with the item leading to `screen`
+/// selected.
+fn network_conf(config: &Config, screen: Screen) -> State {
+    let selected = config
+        .menus
+        .get(NETWORK_CONF_MENU)
+        .and_then(|menu| menu.position(&Target::Screen(screen)))
+        .unwrap_or(0);
+    State::Menu(NETWORK_CONF_MENU.to_string(), selected)
+}
+
+/// Returns the Network screen showing the details of the wireless interface,
+/// or the list of interfaces if it is not configured.
+fn network_page(config: &Config) -> State {
+    let page = config
+        .network
+        .interfaces
+        .iter()
+        .position(|iface| iface.name == WLAN_IFACE)
+        .map_or(0, |i| i + 1);
+    State::Network(page)
+}
+
+/// Returns whether `page` of the Network screen shows the access point.
+fn ap_page(config: &Config, page: usize) -> bool {
+    page.checked_sub(1)
+        .and_then(|i| config.network.interfaces.get(i))
+        .map_or(false, |iface| iface.role == Role::Ap)
+}
+
+/// Shares the current state with the JSON-RPC server.
+fn publish(current: &Arc<Mutex<State>>, state: &State) {
+    match current.lock() {
+        Ok(mut current) => *current = state.clone(),
+        Err(e) => warn!("Failed to publish current state: {}", e),
+    }
+}
+
+// Join(ssid, passphrase)
+//   saves the credentials for `ssid`, then connects to it
+// Keyboard(ssid, keyboard)
+//   the passphrase for `ssid` being entered with the on-screen keyboard
+// Menu(id, selected)
+//   a menu declared in the configuration, with the item at index `selected`
+//   highlighted
+// Network(page)
+//   0 - the state or address of every configured interface
+//   n - the details of interface n
 // NetworkMode
 //   0 - Client Mode
 //   1 - Access Point Mode
-// OledPower
-//   0 - Off
-//   1 - On
+// Open(networks, selected)
+//   explains that the open network at index `selected` of the scan cannot be
+//   joined, then returns to Scan
+// Passphrase(page, remaining)
+//   the access point passphrase, hidden after `remaining` seconds by
+//   returning to the Network screen at `page`
+// Prioritise(networks, selected)
+//   saves the order of `networks` as their priority, then behaves as Saved
+// Qr
+//   0 - Access Point
+//   1 - Web UI
+// Saved(networks, selected)
+//   the saved networks, most preferred first, with the network at index
+//   `selected` highlighted. An empty list is fetched again when entered.
+// SavedOptions(networks, selected, option)
+//   the options for the saved network at index `selected`
+//   0 - Move Up
+//   1 - Move Down
+//   2 - Forget
+// Scan(networks, selected)
+//   the networks found by a scan, strongest first, with the network at index
+//   `selected` highlighted. An empty list is scanned again when entered.
+// Stats
+//   0 - Figures
+//   1 - Graphs
+// Storage(disks, page)
+//   the mounted filesystems, one per page, showing the filesystem at index
+//   `page`. An empty list is fetched again when entered.
 
 impl State {
+    /// Returns the name of the state.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            State::Confirm(..) => "confirm",
+            State::Connect(_) => "connect",
+            State::Forget(_) => "forget",
+            State::Join(..) => "join",
+            State::Keyboard(..) => "keyboard",
+            State::Logo => "logo",
+            State::Menu(..) => "menu",
+            State::Network(_) => "network",
+            State::NetworkMode(_) => "network_mode",
+            State::Open(..) => "open",
+            State::Passphrase(..) => "passphrase",
+            State::Prioritise(..) => "prioritise",
+            State::Qr(_) => "qr",
+            State::Reboot => "reboot",
+            State::Saved(..) => "saved",
+            State::SavedOptions(..) => "saved_options",
+            State::Scan(..) => "scan",
+            State::Shutdown => "shutdown",
+            State::Stats(_) => "stats",
+            State::Storage(..) => "storage",
+        }
+    }
+
+    /// Returns the selected index of states which take one.
+    pub fn selected(&self) -> Option<usize> {
+        match *self {
+            State::Menu(_, selected) => Some(selected),
+            State::Network(page) => Some(page),
+            State::NetworkMode(mode) => Some(mode as usize),
+            State::Open(_, selected) => Some(selected),
+            State::Passphrase(page, _) => Some(page),
+            State::Prioritise(_, selected) => Some(selected),
+            State::Qr(code) => Some(code as usize),
+            State::Saved(_, selected) => Some(selected),
+            State::SavedOptions(_, _, option) => Some(option),
+            State::Scan(_, selected) => Some(selected),
+            State::Stats(page) => Some(page as usize),
+            State::Storage(_, page) => Some(page),
+            _ => None,
+        }
+    }
+
+    /// Returns the question asked before entering the state, if it must be
+    /// confirmed.
+    pub fn prompt(&self) -> Option<String> {
+        match *self {
+            State::Forget(ref ssid) => Some(format!("Forget {}?", ssid)),
+            State::NetworkMode(0) => Some("Enable client mode?".to_string()),
+            State::NetworkMode(1) => Some("Enable AP mode?".to_string()),
+            State::Passphrase(..) => Some("Show passphrase?".to_string()),
+            State::Reboot => Some("Reboot device?".to_string()),
+            State::Shutdown => Some("Shut down device?".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Returns whether entering the state performs an action, such as
+    /// connecting to a network, as well as drawing the result.
+    pub fn performs_action(&self) -> bool {
+        matches!(
+            *self,
+            State::Connect(_)
+                | State::Forget(_)
+                | State::Join(..)
+                | State::NetworkMode(_)
+                | State::Prioritise(..)
+                | State::Reboot
+                | State::Shutdown
+        )
+    }
+
+    /// Returns the state to draw in place of an action state, so that its
+    /// action is not performed again: the screen it returns to once done.
+    /// Other states are returned unchanged.
+    pub fn settled(self, config: &Config) -> State {
+        match self {
+            State::Connect(_) | State::Join(..) => network_page(config),
+            State::Forget(_) => State::Saved(Vec::new(), 0),
+            State::Prioritise(networks, selected) => State::Saved(networks, selected),
+            State::NetworkMode(_) => State::Network(0),
+            State::Reboot | State::Shutdown => State::Menu(config.root.to_string(), 0),
+            state => state,
+        }
+    }
+
+    /// Returns the state reached after one `TICK_INTERVAL` has elapsed, if
+    /// the state changes with time.
+    ///
+    /// Confirmation dialogs count down and are cancelled when the countdown
+    /// reaches zero. The access point passphrase is hidden in the same way.
+    pub fn tick(&self) -> Option<State> {
+        match *self {
+            State::Confirm(_, remaining, ref back) if remaining <= 1 => {
+                info!("Confirmation timed out.");
+                Some(*back.clone())
+            }
+            State::Confirm(ref confirmed, remaining, ref back) => Some(State::Confirm(
+                confirmed.clone(),
+                remaining - 1,
+                back.clone(),
+            )),
+            State::Passphrase(page, remaining) if remaining <= 1 => Some(State::Network(page)),
+            State::Passphrase(page, remaining) => Some(State::Passphrase(page, remaining - 1)),
+            _ => None,
+        }
+    }
+
+    /// Fetches the data held by states which keep it between events, such as
+    /// the results of a Wi-Fi scan. Other states are returned unchanged.
+    pub fn load(self, services: &dyn Services) -> State {
+        match self {
+            State::Saved(ref networks, _) if networks.is_empty() => {
+                debug!("Fetching saved wireless networks.");
+                match wifi::saved(services, WLAN_IFACE) {
+                    Ok(networks) => State::Saved(networks, 0),
+                    Err(e) => {
+                        warn!("Failed to fetch saved networks: {:?}", e);
+                        self
+                    }
+                }
+            }
+            State::Scan(ref networks, _) if networks.is_empty() => {
+                debug!("Scanning for wireless networks.");
+                match wifi::scan(services, WLAN_IFACE) {
+                    Ok(networks) => State::Scan(networks, 0),
+                    Err(e) => {
+                        warn!("Failed to scan for networks: {:?}", e);
+                        self
+                    }
+                }
+            }
+            State::Storage(ref disks, _) if disks.is_empty() => {
+                debug!("Fetching disk usage.");
+                match services.disk_usage() {
+                    Ok(disks) => State::Storage(disks, 0),
+                    Err(e) => {
+                        warn!("Failed to fetch disk usage: {:?}", e);
+                        self
+                    }
+                }
+            }
+            state => state,
+        }
+    }
+
+    /// Returns how often the state should be redrawn with live data, if at
+    /// all.
+    pub fn refresh_interval(&self, config: &Config) -> Option<Duration> {
+        match *self {
+            // menus showing the clock are redrawn on the minute
+            State::Menu(ref id, _) => match config.menus.get(id) {
+                Some(menu) if menu.clock => Some(Duration::from_secs(60)),
+                _ => None,
+            },
+            State::Network(_) => Some(Duration::from_secs(config.refresh.network)),
+            State::Stats(_) => Some(Duration::from_secs(config.refresh.stats)),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the wall clock has crossed a multiple of the refresh
+    /// interval between `last_run` and `now`.
+    pub fn refresh_due(
+        &self,
+        config: &Config,
+        last_run: DateTime<Local>,
+        now: DateTime<Local>,
+    ) -> bool {
+        match self.refresh_interval(config) {
+            Some(interval) => {
+                let interval = interval.as_secs().max(1) as i64;
+                now.timestamp() / interval != last_run.timestamp() / interval
+            }
+            None => false,
+        }
+    }
+
+    /// Redraws states which show live data. Unlike `run`, this never
+    /// performs actions.
+    pub fn refresh(&self, ctx: &mut Context) -> Result<(), MenuError> {
+        let display = ctx.display.as_mut();
+        let services = ctx.services.as_ref();
+        debug!("Refreshing state: {:?}.", self);
+        match *self {
+            State::Menu(ref id, selected) => {
+                if let Some(menu) = ctx.config.menus.get(id) {
+                    state_menu(display, menu, selected)?;
+                }
+            }
+            State::Network(page) => {
+                let config = &ctx.config;
+                state_network(
+                    display,
+                    services,
+                    &config.network.interfaces,
+                    &config.ap,
+                    page,
+                )?;
+            }
+            State::Stats(page) => state_stats(display, services, &ctx.monitor, page)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Returns the event which moves forward from this state: the next page
+    /// of a paged screen, or the selected item of a list if it leads to a
+    /// menu or screen.
+    fn forward(&self, config: &Config) -> Event {
+        match *self {
+            State::Network(_) | State::Qr(_) | State::Stats(_) | State::Storage(..) => Event::Down,
+            State::Saved(..) => Event::A,
+            State::Menu(ref id, selected) => {
+                let item = config
+                    .menus
+                    .get(id)
+                    .and_then(|menu| menu.items.get(selected));
+                match item {
+                    Some(item) => match item.target {
+                        Target::Action(_) => Event::Unknown,
+                        _ => Event::A,
+                    },
+                    None => Event::Unknown,
+                }
+            }
+            _ => Event::Unknown,
+        }
+    }
+
+    /// Returns the event which the state handles for `event`. A double press
+    /// is handled as a second press and chords are ignored. Outside of the
+    /// keyboard, Center, Left and Right are handled as configured in
+    /// `[navigation]`.
+    fn translate(&self, event: Event, config: &Config) -> Event {
+        let event = match event {
+            Event::Double(button) => Event::from(button),
+            Event::Chord(..) => return Event::Unknown,
+            event => event,
+        };
+        let command = match event {
+            Event::Center => Some(config.navigation.center),
+            Event::Left => Some(config.navigation.left),
+            Event::Right => Some(config.navigation.right),
+            _ => None,
+        };
+        match (self, command) {
+            (State::Keyboard(..), _) | (_, None) => event,
+            (_, Some(Command::Select)) => Event::A,
+            (_, Some(Command::Back)) => Event::B,
+            (_, Some(Command::Forward)) => self.forward(config),
+            (_, Some(Command::None)) => Event::Unknown,
+        }
+    }
+
+    /// Returns the action without a state, such as turning the display off,
+    /// which `event` selects, if any. Such actions are run by
+    /// `state_changer` and leave the current state unchanged.
+    pub fn action(&self, event: Event, config: &Config) -> Option<Action> {
+        match *self {
+            State::Menu(ref id, selected) => {
+                selected_action(config, id, selected, self.translate(event, config))
+            }
+            _ => None,
+        }
+    }
+
     /// Determines the next state based on current state and event.
-    pub fn next(self, event: Event) -> State {
+    ///
+    /// Pressing B twice returns to the root menu from any state but the
+    /// keyboard, where it deletes a second character, and pressing A and B
+    /// together opens the maintenance menu, if declared. Other double
+    /// presses are handled as a second press. Outside of the keyboard,
+    /// Center, Left and Right are handled as configured in `[navigation]`.
+    pub fn next(self, event: Event, config: &Config) -> State {
+        match event {
+            Event::Double(Button::B) if !matches!(self, State::Keyboard(..)) => {
+                return State::Menu(config.root.to_string(), 0)
+            }
+            Event::Chord(Button::A, Button::B) if config.menus.contains_key(MAINTENANCE_MENU) => {
+                return State::Menu(MAINTENANCE_MENU.to_string(), 0)
+            }
+            _ => (),
+        }
+        let event = self.translate(event, config);
         match (self, event) {
-            (State::Logo, Event::A) => State::Home(0),
-            (State::Home(_), Event::B) => State::Logo,
-            (State::Home(0), Event::Down) => State::Home(2),
-            (State::Home(0), Event::Up) => State::Home(5),
-            (State::Home(0), Event::A) => State::Network,
-            (State::Home(1), Event::Down) => State::Home(2),
-            (State::Home(1), Event::Up) => State::Home(5),
-            (State::Home(1), Event::A) => State::Network,
-            (State::Home(2), Event::Down) => State::Home(3),
-            (State::Home(2), Event::Up) => State::Home(1),
-            (State::Home(2), Event::A) => State::Stats,
-            (State::Home(3), Event::Down) => State::Home(4),
-            (State::Home(3), Event::Up) => State::Home(2),
-            (State::Home(3), Event::A) => State::OledPower(0),
-            (State::Home(4), Event::Down) => State::Home(5),
-            (State::Home(4), Event::Up) => State::Home(3),
-            (State::Home(4), Event::A) => State::Reboot,
-            (State::Home(5), Event::Down) => State::Home(1),
-            (State::Home(5), Event::Up) => State::Home(4),
-            (State::Home(5), Event::A) => State::Shutdown,
-            (State::Network, Event::A) => State::NetworkConf(0),
-            (State::Network, Event::B) => State::Home(0),
-            (State::NetworkConf(0), Event::A) => State::NetworkMode(0),
-            (State::NetworkConf(0), Event::B) => State::Network,
-            (State::NetworkConf(0), Event::Down) => State::NetworkConf(2),
-            (State::NetworkConf(0), Event::Up) => State::NetworkConf(2),
-            (State::NetworkConf(1), Event::A) => State::NetworkMode(0),
-            (State::NetworkConf(1), Event::B) => State::Network,
-            (State::NetworkConf(1), Event::Down) => State::NetworkConf(2),
-            (State::NetworkConf(1), Event::Up) => State::NetworkConf(2),
-            (State::NetworkConf(2), Event::A) => State::NetworkMode(1),
-            (State::NetworkConf(2), Event::B) => State::Network,
-            (State::NetworkConf(2), Event::Down) => State::NetworkConf(1),
-            (State::NetworkConf(2), Event::Up) => State::NetworkConf(1),
-            (State::NetworkMode(1), Event::B) => State::Network,
-            (State::NetworkMode(1), Event::Down) => State::NetworkConf(1),
-            (State::NetworkMode(1), Event::Up) => State::NetworkConf(1),
-            (State::NetworkMode(0), Event::B) => State::Network,
-            (State::NetworkMode(0), Event::Down) => State::NetworkConf(2),
-            (State::NetworkMode(0), Event::Up) => State::NetworkConf(2),
-            (State::OledPower(0), _) => State::OledPower(1),
-            (State::OledPower(1), Event::Down) => State::Home(4),
-            (State::OledPower(1), Event::Up) => State::Home(2),
-            (State::OledPower(1), Event::A) => State::OledPower(0),
-            (State::Stats, Event::B) => State::Home(0),
+            (State::Confirm(confirmed, _, _), Event::A) => *confirmed,
+            (State::Confirm(_, _, back), Event::B) => *back,
+            (State::Connect(_), Event::A) | (State::Connect(_), Event::B) => network_page(config),
+            (State::Join(..), Event::A) | (State::Join(..), Event::B) => network_page(config),
+            (State::Keyboard(ssid, mut keyboard), event) => match keyboard.press(event) {
+                Outcome::Submit => State::Join(ssid, keyboard.text().clone()),
+                Outcome::Cancel => State::Scan(Vec::new(), 0),
+                _ => State::Keyboard(ssid, keyboard),
+            },
+            (State::Logo, Event::A) => State::Menu(config.root.to_string(), 0),
+            (State::Menu(id, selected), event) => navigate(config, id, selected, event),
+            (State::Network(page), Event::Down) => {
+                State::Network((page + 1) % (config.network.interfaces.len() + 1))
+            }
+            (State::Network(page), Event::Up) => {
+                let pages = config.network.interfaces.len() + 1;
+                State::Network((page + pages - 1) % pages)
+            }
+            (State::Network(page), Event::A) if ap_page(config, page) => {
+                let timeout = config.confirm.timeout;
+                State::Confirm(
+                    Box::new(State::Passphrase(page, timeout)),
+                    timeout,
+                    Box::new(State::Network(page)),
+                )
+            }
+            (State::Network(_), Event::A) => State::Menu(NETWORK_CONF_MENU.to_string(), 0),
+            (State::Network(_), Event::B) => State::Menu(config.root.to_string(), 0),
+            (State::NetworkMode(_), Event::B) => State::Network(0),
+            (State::Passphrase(page, _), Event::A) | (State::Passphrase(page, _), Event::B) => {
+                State::Network(page)
+            }
+            (State::NetworkMode(mode), Event::Down) | (State::NetworkMode(mode), Event::Up) => {
+                // highlight the mode which is not currently active
+                let other = match mode {
+                    0 => Target::Action(Action::AccessPointMode),
+                    _ => Target::Action(Action::ClientMode),
+                };
+                let selected = config
+                    .menus
+                    .get(NETWORK_CONF_MENU)
+                    .and_then(|menu| menu.position(&other))
+                    .unwrap_or(0);
+                State::Menu(NETWORK_CONF_MENU.to_string(), selected)
+            }
+            (State::Forget(_), Event::A) | (State::Forget(_), Event::B) => {
+                State::Saved(Vec::new(), 0)
+            }
+            (State::Prioritise(networks, selected), event) => {
+                State::Saved(networks, selected).next(event, config)
+            }
+            (State::Qr(0), Event::Down) | (State::Qr(0), Event::Up) => State::Qr(1),
+            (State::Qr(_), Event::Down) | (State::Qr(_), Event::Up) => State::Qr(0),
+            (State::Qr(_), Event::B) => network_conf(config, Screen::Qr),
+            (State::Saved(networks, selected), Event::Down) if !networks.is_empty() => {
+                let selected = (selected + 1) % networks.len();
+                State::Saved(networks, selected)
+            }
+            (State::Saved(networks, selected), Event::Up) if !networks.is_empty() => {
+                let selected = (selected + networks.len() - 1) % networks.len();
+                State::Saved(networks, selected)
+            }
+            (State::Saved(networks, selected), Event::A) if !networks.is_empty() => {
+                State::SavedOptions(networks, selected, 0)
+            }
+            (State::Saved(..), Event::B) => network_conf(config, Screen::Saved),
+            (State::SavedOptions(networks, selected, option), Event::Down) => {
+                State::SavedOptions(networks, selected, (option + 1) % 3)
+            }
+            (State::SavedOptions(networks, selected, option), Event::Up) => {
+                State::SavedOptions(networks, selected, (option + 2) % 3)
+            }
+            (State::SavedOptions(mut networks, selected, 0), Event::A) if selected > 0 => {
+                networks.swap(selected, selected - 1);
+                State::Prioritise(networks, selected - 1)
+            }
+            (State::SavedOptions(mut networks, selected, 1), Event::A)
+                if selected + 1 < networks.len() =>
+            {
+                networks.swap(selected, selected + 1);
+                State::Prioritise(networks, selected + 1)
+            }
+            (State::SavedOptions(networks, selected, 2), Event::A) => {
+                match networks.get(selected) {
+                    Some(network) => State::Confirm(
+                        Box::new(State::Forget(network.ssid.to_string())),
+                        config.confirm.timeout,
+                        Box::new(State::SavedOptions(networks, selected, 2)),
+                    ),
+                    None => State::Saved(networks, selected),
+                }
+            }
+            (State::SavedOptions(networks, selected, _), Event::A)
+            | (State::SavedOptions(networks, selected, _), Event::B) => {
+                State::Saved(networks, selected)
+            }
+            (State::Scan(networks, selected), Event::Down) if !networks.is_empty() => {
+                let selected = (selected + 1) % networks.len();
+                State::Scan(networks, selected)
+            }
+            (State::Scan(networks, selected), Event::Up) if !networks.is_empty() => {
+                let selected = (selected + networks.len() - 1) % networks.len();
+                State::Scan(networks, selected)
+            }
+            (State::Scan(networks, selected), Event::A) => match networks.get(selected) {
+                Some(network) if network.saved => State::Connect(network.ssid.to_string()),
+                Some(network) if network.secured => State::Keyboard(
+                    network.ssid.to_string(),
+                    Keyboard::new(MIN_PASSPHRASE_LEN, MAX_PASSPHRASE_LEN),
+                ),
+                // peach-network only saves networks with a passphrase
+                Some(_) => State::Open(networks, selected),
+                // scan again
+                None => State::Scan(Vec::new(), 0),
+            },
+            (State::Scan(..), Event::B) => network_conf(config, Screen::Scan),
+            (State::Open(networks, selected), Event::A)
+            | (State::Open(networks, selected), Event::B) => State::Scan(networks, selected),
+            (State::Stats(0), Event::Down) | (State::Stats(0), Event::Up) => State::Stats(1),
+            (State::Stats(_), Event::Down) | (State::Stats(_), Event::Up) => State::Stats(0),
+            (State::Stats(_), Event::B) => State::Menu(config.root.to_string(), 0),
+            (State::Storage(disks, page), Event::Down) if !disks.is_empty() => {
+                let page = (page + 1) % disks.len();
+                State::Storage(disks, page)
+            }
+            (State::Storage(disks, page), Event::Up) if !disks.is_empty() => {
+                let page = (page + disks.len() - 1) % disks.len();
+                State::Storage(disks, page)
+            }
+            (State::Storage(..), Event::B) => {
+                // return to the item which opened the screen
+                let selected = config
+                    .menus
+                    .get(&config.root)
+                    .and_then(|menu| menu.position(&Target::Screen(Screen::Storage)))
+                    .unwrap_or(0);
+                State::Menu(config.root.to_string(), selected)
+            }
             // return current state if combination is unmatched
             (s, _) => s,
         }
     }
 
     /// Executes state-specific logic for current state.
-    pub fn run(&self) -> Result<(), PeachError> {
+    pub fn run(&self, ctx: &mut Context) -> Result<(), MenuError> {
+        let config = &ctx.config;
+        let display = ctx.display.as_mut();
+        let services = ctx.services.as_ref();
         match *self {
-            // home: root
-            State::Home(0) => {
-                info!("State changed to: Home 0.");
-                state_home(0)?;
-            }
-            // home: networking
-            State::Home(1) => {
-                info!("State changed to: Home 1.");
-                state_home(1)?;
-            }
-            // home: system stats
-            State::Home(2) => {
-                info!("State changed to: Home 2.");
-                state_home(2)?;
-            }
-            // home: display off
-            State::Home(3) => {
-                info!("State changed to: Home 3.");
-                state_home(3)?;
-            }
-            // home: reboot
-            State::Home(4) => {
-                info!("State changed to: Home 4.");
-                state_home(4)?;
-            }
-            // home: shutdown
-            State::Home(5) => {
-                info!("State changed to: Home 5.");
-                state_home(5)?;
-            }
-            // home: unknown
-            State::Home(_) => {
-                info!("State changed to: Home _.");
+            State::Confirm(ref confirmed, remaining, _) => {
+                info!("State changed to: Confirm {:?} {}.", confirmed, remaining);
+                if let Some(prompt) = confirmed.prompt() {
+                    let yes = config.buttons.label(Button::A);
+                    let no = config.buttons.label(Button::B);
+                    state_confirm(display, &prompt, &yes, &no, remaining)?;
+                }
             }
-            State::Logo => {
-                info!("State changed to: Logo.");
-                state_logo()?;
+            State::Connect(ref ssid) => {
+                info!("State changed to: Connect {}.", ssid);
+                state_connect(display, services, ssid)?;
+            }
+            // turning the display off is handled by `state_changer`
+            State::Forget(ref ssid) => {
+                info!("State changed to: Forget {}.", ssid);
+                state_forget(display, services, ssid)?;
             }
-            State::Network => {
-                info!("State changed to: Network.");
-                state_network()?;
+            State::Join(ref ssid, ref passphrase) => {
+                info!("State changed to: Join {}.", ssid);
+                state_join(display, services, ssid, passphrase.as_str())?;
             }
-            State::NetworkConf(0) => {
-                info!("State changed to: NetworkConf 0.");
-                state_network_conf(0)?;
+            State::Keyboard(ref ssid, ref keyboard) => {
+                info!("State changed to: Keyboard {}.", ssid);
+                state_keyboard(display, ssid, keyboard)?;
             }
-            State::NetworkConf(1) => {
-                info!("State changed to: NetworkConf 1.");
-                state_network_conf(1)?;
+            State::Menu(ref id, selected) => {
+                info!("State changed to: Menu {} {}.", id, selected);
+                match config.menus.get(id) {
+                    Some(menu) => state_menu(display, menu, selected)?,
+                    None => warn!("Menu '{}' is not declared.", id),
+                }
             }
-            State::NetworkConf(2) => {
-                info!("State changed to: NetworkConf 2.");
-                state_network_conf(2)?;
+            State::Open(ref networks, selected) => {
+                info!("State changed to: Open {}.", selected);
+                if let Some(network) = networks.get(selected) {
+                    state_open(display, &network.ssid)?;
+                }
+            }
+            State::Logo => {
+                info!("State changed to: Logo.");
+                state_logo(display)?;
             }
-            State::NetworkConf(_) => {
-                info!("State changed to: NetworkConf _.");
+            State::Network(page) => {
+                info!("State changed to: Network {}.", page);
+                state_network(
+                    display,
+                    services,
+                    &config.network.interfaces,
+                    &config.ap,
+                    page,
+                )?;
             }
             State::NetworkMode(0) => {
                 info!("State changed to: NetworkMode 0.");
-                state_network_mode(0)?;
+                state_network_mode(display, services, 0)?;
             }
             State::NetworkMode(1) => {
                 info!("State changed to: NetworkMode 1.");
-                state_network_mode(1)?;
+                state_network_mode(display, services, 1)?;
             }
             State::NetworkMode(_) => {
                 info!("State changed to: NetworkMode _.");
             }
-            State::OledPower(0) => {
-                info!("State changed to: OledPower 0.");
-                oled_client::power(false)?;
+            State::Passphrase(page, remaining) => {
+                info!("State changed to: Passphrase {} {}.", page, remaining);
+                state_passphrase(display, services, &config.ap, remaining)?;
             }
-            State::OledPower(1) => {
-                info!("State changed to: OledPower 1.");
-                oled_client::power(true)?;
-            }
-            State::OledPower(_) => {
-                info!("State changed to: OledPower _.");
+            State::Qr(code) => {
+                info!("State changed to: Qr {}.", code);
+                state_qr(
+                    display,
+                    services,
+                    &config.network.interfaces,
+                    &config.ap,
+                    code,
+                )?;
             }
             State::Reboot => {
                 info!("State changed to: Reboot.");
-                state_reboot()?;
+                state_reboot(display, services)?;
+            }
+            State::Prioritise(ref networks, selected) => {
+                info!("State changed to: Prioritise {}.", selected);
+                state_prioritise(display, services, networks, selected)?;
+            }
+            State::Saved(ref networks, selected) => {
+                info!("State changed to: Saved {}.", selected);
+                state_saved(display, networks, selected)?;
+            }
+            State::SavedOptions(ref networks, selected, option) => {
+                info!("State changed to: SavedOptions {} {}.", selected, option);
+                state_saved_options(display, networks, selected, option)?;
+            }
+            State::Scan(ref networks, selected) => {
+                info!("State changed to: Scan {}.", selected);
+                state_scan(display, networks, selected)?;
             }
             State::Shutdown => {
                 info!("State changed to: Shutdown.");
-                state_shutdown()?;
+                state_shutdown(display, services)?;
+            }
+            State::Stats(page) => {
+                info!("State changed to: Stats {}.", page);
+                state_stats(display, services, &ctx.monitor, page)?;
             }
-            State::Stats => {
-                info!("State changed to: Stats.");
-                state_stats()?;
+            State::Storage(ref disks, page) => {
+                info!("State changed to: Storage {}.", page);
+                state_storage(display, disks, page, config.storage.threshold)?;
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Recording;
+    use crate::menu::Target;
+    use crate::services::Simulated;
+
+    fn context(display: &Recording) -> Context {
+        let config = Config::default();
+        Context {
+            monitor: Monitor::new(&config.network.interfaces),
+            config,
+            display: Box::new(display.clone()),
+            services: Box::new(Simulated::new()),
+        }
+    }
+
+    fn home(selected: usize) -> State {
+        State::Menu("home".to_string(), selected)
+    }
+
+    #[test]
+    fn actions_are_confirmed() {
+        let config = Config::default();
+        let confirm = home(4).next(Event::A, &config);
+        assert_eq!(
+            confirm,
+            State::Confirm(
+                Box::new(State::Reboot),
+                config.confirm.timeout,
+                Box::new(home(4))
+            )
+        );
+        assert_eq!(confirm.clone().next(Event::A, &config), State::Reboot);
+        assert_eq!(confirm.next(Event::B, &config), home(4));
+    }
+
+    #[test]
+    fn confirmation_is_drawn() {
+        let display = Recording::default();
+        let mut ctx = context(&display);
+        let mut power = DisplayPower::new(Local::now());
+        let state = handle(&mut ctx, home(5), Event::A, &mut power);
+        assert!(matches!(state, State::Confirm(..)));
+        assert!(display
+            .calls()
+            .contains(&"write Shut down device?".to_string()));
+    }
+
+    #[test]
+    fn unmatched_presses_do_not_repeat_actions() {
+        let display = Recording::default();
+        let mut ctx = context(&display);
+        let mut power = DisplayPower::new(Local::now());
+        for event in &[Event::A, Event::Center, Event::Right] {
+            let state = handle(&mut ctx, State::NetworkMode(1), *event, &mut power);
+            assert_eq!(state, State::NetworkMode(1));
+        }
+        let state = handle(
+            &mut ctx,
+            State::Connect("Office".to_string()),
+            Event::Up,
+            &mut power,
+        );
+        assert_eq!(state, State::Connect("Office".to_string()));
+        assert!(display.calls().is_empty());
+    }
+
+    #[test]
+    fn confirmation_times_out() {
+        let confirm = State::Confirm(Box::new(State::Shutdown), 1, Box::new(home(5)));
+        assert_eq!(confirm.tick(), Some(home(5)));
+    }
+
+    #[test]
+    fn wake_does_not_repeat_actions() {
+        let display = Recording::default();
+        let mut ctx = context(&display);
+        let mut power = DisplayPower::new(Local::now());
+        power.off();
+        let state = handle(&mut ctx, State::Reboot, Event::A, &mut power);
+        assert_eq!(state, home(0));
+        let calls = display.calls();
+        assert_eq!(calls.first(), Some(&"power true".to_string()));
+        assert!(!calls.contains(&"write REBOOTING".to_string()));
+    }
+
+    #[test]
+    fn display_off_keeps_state() {
+        let display = Recording::default();
+        let mut ctx = context(&display);
+        let mut power = DisplayPower::new(Local::now());
+        let state = handle(&mut ctx, home(3), Event::A, &mut power);
+        assert_eq!(state, home(3));
+        assert_eq!(display.calls(), vec!["power false".to_string()]);
+        assert_eq!(power.phase(), Phase::Off);
+    }
+
+    #[test]
+    fn back_navigation() {
+        let config = Config::default();
+        assert_eq!(home(2).next(Event::B, &config), State::Logo);
+        assert_eq!(State::Stats(1).next(Event::B, &config), home(0));
+        assert_eq!(
+            State::Menu(NETWORK_CONF_MENU.to_string(), 1).next(Event::B, &config),
+            State::Network(0)
+        );
+        assert_eq!(
+            State::Scan(Vec::new(), 0).next(Event::B, &config),
+            State::Menu(NETWORK_CONF_MENU.to_string(), 0)
+        );
+        assert_eq!(
+            State::Open(Vec::new(), 2).next(Event::B, &config),
+            State::Scan(Vec::new(), 2)
+        );
+    }
+
+    #[test]
+    fn gestures() {
+        let config = Config::default();
+        assert_eq!(
+            State::Stats(1).next(Event::Double(Button::B), &config),
+            home(0)
+        );
+        assert_eq!(
+            State::Network(0).next(Event::Double(Button::Down), &config),
+            State::Network(1)
+        );
+        assert_eq!(
+            State::Logo.next(Event::Chord(Button::A, Button::B), &config),
+            State::Menu(MAINTENANCE_MENU.to_string(), 0)
+        );
+
+        let mut typed = Keyboard::new(MIN_PASSPHRASE_LEN, MAX_PASSPHRASE_LEN);
+        typed.press(Event::A);
+        let mut keyboard = typed.clone();
+        keyboard.press(Event::A);
+        assert_eq!(
+            State::Keyboard("Office".to_string(), keyboard).next(Event::Double(Button::B), &config),
+            State::Keyboard("Office".to_string(), typed)
+        );
+    }
+
+    #[test]
+    fn back_targets_are_confirmed() {
+        let mut config = Config::default();
+        config.menus.get_mut("home").unwrap().back = Some(Target::Action(Action::Shutdown));
+        assert_eq!(
+            home(1).next(Event::B, &config),
+            State::Confirm(
+                Box::new(State::Shutdown),
+                config.confirm.timeout,
+                Box::new(home(1))
+            )
+        );
+    }
+}
@@ -1,44 +1,57 @@
-use std::{process, thread, time};
+use std::{thread, time};
 
 use chrono::{DateTime, Local};
-use log::info;
-
-use peach_lib::error::PeachError;
-use peach_lib::network_client;
-use peach_lib::oled_client;
-use peach_lib::stats_client;
-
-pub fn state_network_mode(mode: u8) -> Result<(), PeachError> {
+use log::warn;
+
+use crate::bitmap::Bitmap;
+use crate::config::Ap;
+use crate::display::{self, Display};
+use crate::error::MenuError;
+use crate::format::{self, COLUMNS};
+use crate::keyboard::{Keyboard, Page};
+use crate::menu::Menu;
+use crate::monitor::Monitor;
+use crate::network::{self, Interface, Role};
+use crate::qr;
+use crate::services::Services;
+use crate::structs::DiskUsage;
+use crate::wifi::{self, AccessPoint, SavedNetwork, WLAN_IFACE};
+
+pub fn state_network_mode(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    mode: u8,
+) -> Result<(), MenuError> {
     match mode {
         0 => {
-            oled_client::clear()?;
-            oled_client::write(24, 16, "ACTIVATING", "6x8")?;
-            oled_client::write(24, 27, "WIRELESS", "6x8")?;
-            oled_client::write(24, 38, "CONNECTION...", "6x8")?;
-            oled_client::flush()?;
+            display.clear()?;
+            display.write(24, 16, "ACTIVATING", "6x8")?;
+            display.write(24, 27, "WIRELESS", "6x8")?;
+            display.write(24, 38, "CONNECTION...", "6x8")?;
+            display.flush()?;
 
-            network_client::activate_client()?;
+            services.activate_client()?;
 
-            oled_client::clear()?;
-            oled_client::write(0, 0, "> Client mode", "6x8")?;
-            oled_client::write(0, 9, "  Access point mode", "6x8")?;
-            oled_client::flush()?;
+            display.clear()?;
+            display.write(0, 0, "> Client mode", "6x8")?;
+            display.write(0, 9, "  Access point mode", "6x8")?;
+            display.flush()?;
 
             Ok(())
         }
         1 => {
-            oled_client::clear()?;
-            oled_client::write(27, 16, "DEPLOYING", "6x8")?;
-            oled_client::write(27, 27, "ACCESS", "6x8")?;
-            oled_client::write(27, 38, "POINT...", "6x8")?;
-            oled_client::flush()?;
+            display.clear()?;
+            display.write(27, 16, "DEPLOYING", "6x8")?;
+            display.write(27, 27, "ACCESS", "6x8")?;
+            display.write(27, 38, "POINT...", "6x8")?;
+            display.flush()?;
 
-            network_client::activate_ap()?;
+            services.activate_ap()?;
 
-            oled_client::clear()?;
-            oled_client::write(0, 0, "  Client mode", "6x8")?;
-            oled_client::write(0, 9, "> Access point mode", "6x8")?;
-            oled_client::flush()?;
+            display.clear()?;
+            display.write(0, 0, "  Client mode", "6x8")?;
+            display.write(0, 9, "> Access point mode", "6x8")?;
+            display.flush()?;
 
             Ok(())
         }
@@ -46,274 +59,791 @@ pub fn state_network_mode(mode: u8) -> Result<(), PeachError> {
     }
 }
 
-pub fn state_home(selected: u8) -> Result<(), PeachError> {
-    // match on `selected`
-    match selected {
-        // Home: root
-        0 => {
-            let dt: DateTime<Local> = Local::now();
-            let t = format!("{}", dt.time().format("%H:%M"));
-
-            oled_client::clear()?;
-            oled_client::write(96, 0, &t, "6x8")?;
-            oled_client::write(0, 0, "PeachCloud", "6x8")?;
-            oled_client::write(0, 18, "> Networking", "6x8")?;
-            oled_client::write(0, 27, "  System Stats", "6x8")?;
-            oled_client::write(0, 36, "  Display Off", "6x8")?;
-            oled_client::write(0, 45, "  Reboot", "6x8")?;
-            oled_client::write(0, 54, "  Shutdown", "6x8")?;
-            oled_client::write(100, 54, "v0.2", "6x8")?;
-            oled_client::flush()?;
+/// Renders a configured menu with the item at index `selected` highlighted.
+///
+/// Items start on the first line, or below the title if the menu has one.
+/// The list scrolls to keep the selected item visible.
+pub fn state_menu(
+    display: &mut dyn Display,
+    menu: &Menu,
+    selected: usize,
+) -> Result<(), MenuError> {
+    display.clear()?;
+
+    let mut top = 0;
+    if let Some(ref title) = menu.title {
+        // leave room for the clock and a space before it
+        let columns = if menu.clock { COLUMNS - 6 } else { COLUMNS };
+        display.write(0, 0, &format::fit(title, columns), "6x8")?;
+        top = 18;
+    }
+    if menu.clock {
+        let dt: DateTime<Local> = Local::now();
+        let t = format!("{}", dt.time().format("%H:%M"));
+        display.write(format::right(&t), 0, &t, "6x8")?;
+    }
+    if let Some(ref footer) = menu.footer {
+        let footer = format::fit(footer, COLUMNS);
+        display.write(format::right(&footer), 54, &footer, "6x8")?;
+    }
 
-            Ok(())
-        }
-        // Home: networking
-        1 => {
-            oled_client::write(0, 18, "> ", "6x8")?;
-            oled_client::write(0, 27, "  ", "6x8")?;
-            oled_client::write(0, 36, "  ", "6x8")?;
-            oled_client::write(0, 45, "  ", "6x8")?;
-            oled_client::write(0, 54, "  ", "6x8")?;
-            oled_client::flush()?;
+    // number of 9px lines which fit between `top` and the bottom of the screen
+    let rows = ((64 - top) / 9) as usize;
+    let first = if selected >= rows {
+        selected + 1 - rows
+    } else {
+        0
+    };
+    for (i, item) in menu.items.iter().enumerate().skip(first).take(rows) {
+        let cursor = if i == selected { ">" } else { " " };
+        let line = format!("{} {}", cursor, item.label);
+        let y = top + 9 * (i - first) as i32;
+        write_line(display, 0, y, &line)?;
+    }
+    display.flush()?;
 
-            Ok(())
-        }
-        // Home: system stats
-        2 => {
-            oled_client::write(0, 18, "  ", "6x8")?;
-            oled_client::write(0, 27, "> ", "6x8")?;
-            oled_client::write(0, 36, "  ", "6x8")?;
-            oled_client::write(0, 45, "  ", "6x8")?;
-            oled_client::write(0, 54, "  ", "6x8")?;
-            oled_client::flush()?;
+    Ok(())
+}
 
-            Ok(())
-        }
-        // Home: display off
-        3 => {
-            oled_client::write(0, 18, "  ", "6x8")?;
-            oled_client::write(0, 27, "  ", "6x8")?;
-            oled_client::write(0, 36, "> ", "6x8")?;
-            oled_client::write(0, 45, "  ", "6x8")?;
-            oled_client::write(0, 54, "  ", "6x8")?;
-            oled_client::flush()?;
+/// Renders a confirmation dialog for `prompt`, with the labels of the `yes`
+/// and `no` buttons and the number of seconds `remaining` until it is
+/// cancelled.
+pub fn state_confirm(
+    display: &mut dyn Display,
+    prompt: &str,
+    yes: &str,
+    no: &str,
+    remaining: u8,
+) -> Result<(), MenuError> {
+    let countdown = format!("Cancelling in {}s", remaining);
+    let hint = format!("{}=Yes  {}=No", yes, no);
+
+    display.clear()?;
+    write_centered(display, 16, prompt)?;
+    write_centered(display, 30, &hint)?;
+    write_centered(display, 48, &countdown)?;
+    display.flush()?;
 
-            Ok(())
+    Ok(())
+}
+
+pub fn state_logo(display: &mut dyn Display) -> Result<(), MenuError> {
+    display.clear()?;
+    display.draw(&PEACH_LOGO, 64, 64, 32, 0)?;
+    display.flush()?;
+
+    Ok(())
+}
+
+/// Shown in place of access point settings which cannot be read.
+const UNKNOWN: &str = "unknown";
+
+/// The number of interfaces listed on the first page of the Network screen.
+const NETWORK_ROWS: usize = 5;
+
+/// Renders the Network screen. The first page lists the state or address of
+/// every interface and each following page shows the details of one
+/// interface. The access point page offers to show its passphrase instead of
+/// the network configuration menu.
+pub fn state_network(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    interfaces: &[Interface],
+    ap: &Ap,
+    page: usize,
+) -> Result<(), MenuError> {
+    let position = format!("{}/{}", page + 1, interfaces.len() + 1);
+    let mut hint = "> Configuration";
+
+    display.clear()?;
+    match page.checked_sub(1).and_then(|i| interfaces.get(i)) {
+        None => {
+            display.write(0, 0, "NETWORK", "6x8")?;
+            for (i, iface) in interfaces.iter().take(NETWORK_ROWS).enumerate() {
+                let status = network::status(services, iface);
+                let detail = match status.ip {
+                    Some(ip) => ip,
+                    None => status.state.label().to_string(),
+                };
+                let line = format!("{:<6}{}", status.name, detail);
+                write_line(display, 0, 10 + 9 * i as i32, &line)?;
+            }
+        }
+        Some(iface) => {
+            let status = network::status(services, iface);
+            let ip = status.ip.as_deref().unwrap_or("_");
+            write_line(display, 0, 0, &status.name.to_uppercase())?;
+            write_line(display, 0, 10, &format!("ROLE {}", status.role.label()))?;
+            write_line(display, 0, 19, &format!("STATUS {}", status.state.label()))?;
+            write_line(display, 0, 28, &format!("IP {}", ip))?;
+            match status.role {
+                Role::Client if status.state.is_active() => {
+                    let ssid = match services.ssid(&iface.name) {
+                        Ok(ssid) => ssid,
+                        Err(_) => "Not connected".to_string(),
+                    };
+                    let rssi = match services.rssi(&iface.name).map(|rssi| rssi.trim().parse()) {
+                        Ok(Ok(rssi)) => format::dbm(rssi),
+                        _ => "_".to_string(),
+                    };
+                    write_line(display, 0, 37, &format!("NETWORK {}", ssid))?;
+                    write_line(display, 0, 46, &format!("SIGNAL {}", rssi))?;
+                }
+                Role::Ap => {
+                    let settings = network::ap_settings(services, ap);
+                    let ssid = settings.ssid.as_deref().unwrap_or(UNKNOWN);
+                    write_line(display, 0, 37, &format!("NETWORK {}", ssid))?;
+                    let mut details = Vec::new();
+                    if let Some(channel) = settings.channel {
+                        details.push(format!("CH {}", channel));
+                    }
+                    if status.state.is_active() {
+                        if let Ok(clients) = services.ap_clients(&iface.name) {
+                            details.push(format!("CLIENTS {}", clients));
+                        }
+                    }
+                    write_line(display, 0, 46, &details.join(" "))?;
+                    hint = "> Show passphrase";
+                }
+                _ => (),
+            }
         }
-        // Home: reboot
-        4 => {
-            oled_client::write(0, 18, "  ", "6x8")?;
-            oled_client::write(0, 27, "  ", "6x8")?;
-            oled_client::write(0, 36, "  ", "6x8")?;
-            oled_client::write(0, 45, "> ", "6x8")?;
-            oled_client::write(0, 54, "  ", "6x8")?;
-            oled_client::flush()?;
+    }
+    display.write(format::right(&position), 0, &position, "6x8")?;
+    display.write(0, 55, hint, "6x8")?;
+    display.flush()?;
 
-            Ok(())
+    Ok(())
+}
+
+/// Shows the SSID and passphrase of the access point, with the number of
+/// seconds until they are hidden again.
+pub fn state_passphrase(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    ap: &Ap,
+    remaining: u8,
+) -> Result<(), MenuError> {
+    let settings = network::ap_settings(services, ap);
+    let passphrase: Option<Vec<char>> = settings
+        .passphrase
+        .map(|passphrase| passphrase.as_str().chars().collect());
+
+    display.clear()?;
+    write_line(display, 0, 0, settings.ssid.as_deref().unwrap_or(UNKNOWN))?;
+    match passphrase {
+        None => display.write(0, 10, "Passphrase unknown", "6x8")?,
+        Some(passphrase) if passphrase.is_empty() => {
+            display.write(0, 10, "No passphrase", "6x8")?
+        }
+        Some(passphrase) => {
+            // passphrases are up to 63 characters, so wrap them over three lines
+            for (i, line) in passphrase.chunks(COLUMNS).take(3).enumerate() {
+                let line: String = line.iter().collect();
+                display.write(0, 10 + 9 * i as i32, &line, "6x8")?;
+            }
         }
-        // Home: shutdown
-        5 => {
-            oled_client::write(0, 18, "  ", "6x8")?;
-            oled_client::write(0, 27, "  ", "6x8")?;
-            oled_client::write(0, 36, "  ", "6x8")?;
-            oled_client::write(0, 45, "  ", "6x8")?;
-            oled_client::write(0, 54, "> ", "6x8")?;
-            oled_client::flush()?;
+    }
+    write_line(display, 0, 46, &format!("Hidden in {}s", remaining))?;
+    display.write(0, 55, "> Network", "6x8")?;
+    display.flush()?;
 
-            Ok(())
+    Ok(())
+}
+
+/// Renders the networks found by a scan, a page at a time, with the network
+/// at index `selected` highlighted. Secured networks are marked with a lock.
+pub fn state_scan(
+    display: &mut dyn Display,
+    networks: &[AccessPoint],
+    selected: usize,
+) -> Result<(), MenuError> {
+    display.clear()?;
+    display.write(0, 0, "NETWORKS", "6x8")?;
+
+    if networks.is_empty() {
+        display.write(0, 19, "No networks found", "6x8")?;
+        display.write(0, 55, "> Scan again", "6x8")?;
+        display.flush()?;
+        return Ok(());
+    }
+
+    let first = draw_page(display, selected, networks.len())?;
+    for (i, network) in networks.iter().enumerate().skip(first).take(PAGE_ROWS) {
+        let y = 10 + 9 * (i - first) as i32;
+        let cursor = if i == selected { ">" } else { " " };
+        // leave room for the lock and the signal strength, e.g. `-52 dBm`
+        let line = format!("{} {}", cursor, network.ssid);
+        display.write(0, y, &format::fit(&line, 13), "6x8")?;
+        if network.secured {
+            display.draw(&LOCK_ICON, 6, 8, 78, y)?;
         }
-        // outlier
-        _ => Ok(()),
+        let signal = format::fit(&format::dbm(network.signal), 7);
+        display.write(format::right(&signal), y, &signal, "6x8")?;
     }
+    display.flush()?;
+
+    Ok(())
 }
 
-pub fn state_logo() -> Result<(), PeachError> {
-    let bytes = PEACH_LOGO.to_vec();
-    oled_client::clear()?;
-    oled_client::draw(bytes, 64, 64, 32, 0)?;
-    oled_client::flush()?;
+/// Explains that the open network `ssid` cannot be joined, since
+/// `peach-network` only saves networks with a passphrase.
+pub fn state_open(display: &mut dyn Display, ssid: &str) -> Result<(), MenuError> {
+    display.clear()?;
+    write_centered(display, 10, ssid)?;
+    write_centered(display, 24, "Open networks cannot")?;
+    write_centered(display, 33, "be joined from the")?;
+    write_centered(display, 42, "menu")?;
+    display.write(0, 55, "> Back", "6x8")?;
+    display.flush()?;
 
     Ok(())
 }
 
-pub fn state_network() -> Result<(), PeachError> {
-    let status = match network_client::state("wlan0") {
-        Ok(state) => state,
-        Err(_) => "Error".to_string(),
+/// Connects to the saved network `ssid` and reports whether the request
+/// succeeded.
+pub fn state_connect(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    ssid: &str,
+) -> Result<(), MenuError> {
+    display.clear()?;
+    write_centered(display, 16, "CONNECTING TO")?;
+    write_centered(display, 27, ssid)?;
+    display.flush()?;
+
+    let status = match services.connect(ssid, WLAN_IFACE) {
+        Ok(_) => "JOINING NETWORK",
+        Err(e) => {
+            warn!("Failed to connect to {}: {:?}", ssid, e);
+            "CONNECTION FAILED"
+        }
     };
-    match status.as_ref() {
-        // wlan0 is up or dormant
-        // Network: Client mode
-        "up" | "dormant" => {
-            let show_status = format!("STATUS {}", status);
-            let ip = match network_client::ip("wlan0") {
-                Ok(ip) => ip,
-                Err(_) => "x.x.x.x".to_string(),
-            };
-            let show_ip = format!("IP {}", ip);
-            let ssid = match network_client::ssid("wlan0") {
-                Ok(ssid) => ssid,
-                Err(_) => "Not connected".to_string(),
-            };
-            let show_ssid = format!("NETWORK {}", ssid);
-            let rssi = match network_client::rssi("wlan0") {
-                Ok(rssi) => rssi,
-                Err(_) => "_".to_string(),
-            };
-            let show_rssi = format!("SIGNAL {}dBm", rssi);
-            let config = "> Configuration";
-
-            oled_client::clear()?;
-            oled_client::write(0, 0, "MODE Client", "6x8")?;
-            oled_client::write(0, 9, &show_status, "6x8")?;
-            oled_client::write(0, 18, &show_ssid, "6x8")?;
-            oled_client::write(0, 27, &show_ip, "6x8")?;
-            oled_client::write(0, 36, &show_rssi, "6x8")?;
-            oled_client::write(0, 54, config, "6x8")?;
-            oled_client::flush()?;
 
-            Ok(())
+    display.clear()?;
+    write_centered(display, 16, status)?;
+    write_centered(display, 27, ssid)?;
+    display.write(0, 55, "> Network", "6x8")?;
+    display.flush()?;
+
+    Ok(())
+}
+
+/// Renders the on-screen keyboard for entering the passphrase of `ssid`,
+/// with the highlighted key framed. The accepted length is shown in place of
+/// `ssid` after a passphrase which is too short has been submitted.
+pub fn state_keyboard(
+    display: &mut dyn Display,
+    ssid: &str,
+    keyboard: &Keyboard,
+) -> Result<(), MenuError> {
+    // keep the end of long passphrases in view, followed by a cursor
+    let masked = keyboard.masked();
+    let skip = masked.chars().count().saturating_sub(COLUMNS - 1);
+    let text: String = masked.chars().skip(skip).chain("_".chars()).collect();
+    let (row, col) = keyboard.cursor();
+
+    display.clear()?;
+    match keyboard.rejected() {
+        Some((min, max)) => write_line(display, 0, 0, &format!("{}-{} characters", min, max))?,
+        None => write_line(display, 0, 0, ssid)?,
+    }
+    display.write(0, 9, &text, "6x8")?;
+
+    for (r, keys) in keyboard.rows().iter().enumerate() {
+        let y = 19 + 9 * r as i32;
+        for (c, key) in keys.chars().enumerate() {
+            let x = 6 + 12 * c as i32;
+            display.write(x, y, &key.to_string(), "6x8")?;
+            if (r, c) == (row, col) {
+                draw(display, &Bitmap::frame(10, 10), x - 2, y - 1)?;
+            }
         }
-        // wlan0 is down
-        // Network: AP mode
-        "down" => {
-            let status = match network_client::state("ap0") {
-                Ok(state) => state,
-                Err(_) => "Error".to_string(),
-            };
-            let show_status = format!("STATUS {}", status);
-            let ip = match network_client::ip("ap0") {
-                Ok(ip) => ip,
-                Err(_) => "x.x.x.x".to_string(),
-            };
-            let show_ip = format!("IP {}", ip);
-            let ssid = "peach";
-            let show_ssid = format!("NETWORK {}", ssid);
-            let config = "> Configuration";
-
-            oled_client::clear()?;
-            oled_client::write(0, 0, "MODE Access Point", "6x8")?;
-            oled_client::write(0, 9, &show_status, "6x8")?;
-            oled_client::write(0, 18, &show_ssid, "6x8")?;
-            oled_client::write(0, 27, &show_ip, "6x8")?;
-            oled_client::write(0, 54, config, "6x8")?;
-            oled_client::flush()?;
+    }
+
+    let shift = match keyboard.page() {
+        Page::Upper => "abc",
+        _ => "ABC",
+    };
+    let symbols = match keyboard.page() {
+        Page::Symbols => "abc",
+        _ => "#+=",
+    };
+    let y = 19 + 9 * keyboard.rows().len() as i32;
+    for (c, label) in [shift, symbols, "Done"].iter().enumerate() {
+        let x = 6 + 42 * c as i32;
+        display.write(x, y, label, "6x8")?;
+        if (keyboard.rows().len(), c) == (row, col) {
+            let width = 6 * label.len() as u32 + 4;
+            draw(display, &Bitmap::frame(width, 10), x - 2, y - 1)?;
+        }
+    }
+    display.flush()?;
+
+    Ok(())
+}
+
+/// Saves `passphrase` for the network `ssid` and connects to it.
+pub fn state_join(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    ssid: &str,
+    passphrase: &str,
+) -> Result<(), MenuError> {
+    display.clear()?;
+    write_centered(display, 16, "SAVING")?;
+    write_centered(display, 27, ssid)?;
+    display.flush()?;
+
+    match services.add(ssid, passphrase) {
+        Ok(_) => state_connect(display, services, ssid),
+        Err(e) => {
+            warn!("Failed to save {}: {:?}", ssid, e);
+            display.clear()?;
+            write_centered(display, 16, "FAILED TO SAVE")?;
+            write_centered(display, 27, ssid)?;
+            display.write(0, 55, "> Network", "6x8")?;
+            display.flush()?;
 
             Ok(())
         }
-        // outlier
-        // TODO: account for iface states other than 'up' and 'down'
-        _ => Ok(()),
     }
 }
 
-pub fn state_network_conf(selected: u8) -> Result<(), PeachError> {
-    // match on `selected`
-    match selected {
-        // NetworkConf: root
+/// Renders a QR code on the left-hand side of the display, with a caption on
+/// the right.
+///
+/// Code 0 joins the access point and code 1 opens the web interface at the
+/// address of the first configured interface which has one.
+pub fn state_qr(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    interfaces: &[Interface],
+    ap: &Ap,
+    code: u8,
+) -> Result<(), MenuError> {
+    let mut caption = vec!["SCAN TO".to_string()];
+    let data = match code {
         0 => {
-            oled_client::clear()?;
-            oled_client::write(0, 0, "> Client Mode", "6x8")?;
-            oled_client::write(0, 9, "  Access Point Mode", "6x8")?;
-            oled_client::flush()?;
+            let settings = network::ap_settings(services, ap);
+            let (ssid, passphrase) = match (settings.ssid, settings.passphrase) {
+                (Some(ssid), Some(passphrase)) => (ssid, passphrase),
+                _ => {
+                    display.clear()?;
+                    write_centered(display, 27, "AP DETAILS UNKNOWN")?;
+                    display.flush()?;
+                    return Ok(());
+                }
+            };
+            caption.push("JOIN WI-FI".to_string());
+            caption.push(ssid.to_string());
+            qr::wifi_join(&ssid, passphrase.as_str())
+        }
+        _ => {
+            let ip = interfaces
+                .iter()
+                .find_map(|iface| network::status(services, iface).ip);
+            let ip = match ip {
+                Some(ip) => ip,
+                None => {
+                    display.clear()?;
+                    write_centered(display, 27, "NO NETWORK ADDRESS")?;
+                    display.flush()?;
+                    return Ok(());
+                }
+            };
+            caption.push("OPEN WEBUI".to_string());
+            // split long addresses after a dot to fit beside the code
+            match ip[..ip.len().min(10)].rfind('.') {
+                Some(i) if ip.len() > 10 => {
+                    caption.push(ip[..=i].to_string());
+                    caption.push(ip[i + 1..].to_string());
+                }
+                _ => caption.push(ip.to_string()),
+            }
+            format!("http://{}/", ip)
+        }
+    };
+    let image = qr::bitmap(&data)?;
+    let position = format!("{}/2", code.min(1) + 1);
+
+    display.clear()?;
+    draw(display, &image, 0, 0)?;
+    for (i, line) in caption.iter().enumerate() {
+        // leave a blank line between the heading and the details
+        let y = if i < 2 {
+            9 * i as i32
+        } else {
+            9 * (i + 1) as i32
+        };
+        write_line(display, 68, y, line)?;
+    }
+    display.write(format::right(&position), 55, &position, "6x8")?;
+    display.flush()?;
 
-            Ok(())
+    Ok(())
+}
+
+/// Renders the saved networks, a page at a time, with the network at index
+/// `selected` highlighted. The connected network is marked with a `*`.
+pub fn state_saved(
+    display: &mut dyn Display,
+    networks: &[SavedNetwork],
+    selected: usize,
+) -> Result<(), MenuError> {
+    display.clear()?;
+    display.write(0, 0, "SAVED", "6x8")?;
+
+    if networks.is_empty() {
+        display.write(0, 19, "No saved networks", "6x8")?;
+        display.flush()?;
+        return Ok(());
+    }
+
+    let first = draw_page(display, selected, networks.len())?;
+    for (i, network) in networks.iter().enumerate().skip(first).take(PAGE_ROWS) {
+        let y = 10 + 9 * (i - first) as i32;
+        let cursor = if i == selected { ">" } else { " " };
+        // leave room for the connection marker
+        let line = format!("{} {}", cursor, network.ssid);
+        display.write(0, y, &format::fit(&line, COLUMNS - 2), "6x8")?;
+        if network.connected {
+            display.write(122, y, "*", "6x8")?;
         }
-        // NetworkConf: client
-        1 => {
-            oled_client::write(0, 0, "> ", "6x8")?;
-            oled_client::write(0, 9, "  ", "6x8")?;
-            oled_client::flush()?;
+    }
+    display.flush()?;
 
-            Ok(())
+    Ok(())
+}
+
+/// Renders the options for the saved network at index `selected`, with the
+/// option at index `option` highlighted.
+pub fn state_saved_options(
+    display: &mut dyn Display,
+    networks: &[SavedNetwork],
+    selected: usize,
+    option: usize,
+) -> Result<(), MenuError> {
+    let ssid = match networks.get(selected) {
+        Some(network) => &network.ssid,
+        None => return Ok(()),
+    };
+    let priority = format!("Priority {} of {}", selected + 1, networks.len());
+
+    display.clear()?;
+    write_line(display, 0, 0, ssid)?;
+    for (i, label) in ["Move Up", "Move Down", "Forget"].iter().enumerate() {
+        let cursor = if i == option { ">" } else { " " };
+        let line = format!("{} {}", cursor, label);
+        display.write(0, 18 + 9 * i as i32, &line, "6x8")?;
+    }
+    write_line(display, 0, 55, &priority)?;
+    display.flush()?;
+
+    Ok(())
+}
+
+/// Saves the order of `networks` as their priority and renders the list.
+pub fn state_prioritise(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    networks: &[SavedNetwork],
+    selected: usize,
+) -> Result<(), MenuError> {
+    wifi::prioritise(services, WLAN_IFACE, networks)?;
+    state_saved(display, networks, selected)
+}
+
+/// Removes the saved network `ssid` and reports whether the request
+/// succeeded.
+pub fn state_forget(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    ssid: &str,
+) -> Result<(), MenuError> {
+    let status = match services.forget(ssid, WLAN_IFACE) {
+        Ok(_) => "NETWORK FORGOTTEN",
+        Err(e) => {
+            warn!("Failed to forget {}: {:?}", ssid, e);
+            "FAILED TO FORGET"
         }
-        // NetworkConf: ap
-        2 => {
-            oled_client::write(0, 0, "  ", "6x8")?;
-            oled_client::write(0, 9, "> ", "6x8")?;
-            oled_client::flush()?;
+    };
 
-            Ok(())
+    display.clear()?;
+    write_centered(display, 16, status)?;
+    write_centered(display, 27, ssid)?;
+    display.write(0, 55, "> Saved networks", "6x8")?;
+    display.flush()?;
+
+    Ok(())
+}
+
+/// Renders the usage of the filesystem at index `page` of `disks`.
+///
+/// The filesystem name is drawn in inverse video when its usage is at or
+/// above `threshold` percent.
+pub fn state_storage(
+    display: &mut dyn Display,
+    disks: &[DiskUsage],
+    page: usize,
+    threshold: u32,
+) -> Result<(), MenuError> {
+    display.clear()?;
+    display.write(0, 0, "STORAGE", "6x8")?;
+
+    let disk = match disks.get(page) {
+        Some(disk) => disk,
+        None => {
+            display.write(0, 19, "No filesystems found", "6x8")?;
+            display.flush()?;
+            return Ok(());
         }
-        // outlier
-        _ => Ok(()),
+    };
+    let position = format!("{}/{}", page + 1, disks.len());
+    let name = format::fit(
+        disk.filesystem.as_ref().unwrap_or(&disk.mountpoint),
+        COLUMNS,
+    );
+    let mountpoint = format!("MOUNT {}", disk.mountpoint);
+    let used = format!("USED {}", format::bytes(disk.one_k_blocks_used * 1024));
+    let free = format!("FREE {}", format::bytes(disk.one_k_blocks_free * 1024));
+    let percentage = format::percent(disk.used_percentage as f32);
+
+    display.write(format::right(&position), 0, &position, "6x8")?;
+    if disk.used_percentage >= threshold {
+        draw(display, &Bitmap::inverted_text(&name, 128), 0, 10)?;
+    } else {
+        display.write(0, 10, &name, "6x8")?;
     }
+    write_line(display, 0, 19, &mountpoint)?;
+    write_line(display, 0, 28, &used)?;
+    write_line(display, 0, 37, &free)?;
+    draw(display, &Bitmap::bar(98, 8, disk.used_percentage), 0, 48)?;
+    display.write(format::right(&percentage), 48, &percentage, "6x8")?;
+    display.flush()?;
+
+    Ok(())
 }
 
-pub fn state_reboot() -> Result<(), PeachError> {
-    oled_client::clear()?;
-    oled_client::write(27, 16, "REBOOTING", "6x8")?;
-    oled_client::write(27, 27, "DEVICE...", "6x8")?;
-    oled_client::flush()?;
+/// Writes `text` at `x` and `y`, shortened to fit before the right edge of
+/// the display.
+fn write_line(display: &mut dyn Display, x: i32, y: i32, text: &str) -> Result<(), MenuError> {
+    let text = format::fit(text, format::columns_from(x));
+    display.write(x, y, &text, "6x8")
+}
+
+/// Writes `text` horizontally centered at `y`, shortened to fit the display.
+fn write_centered(display: &mut dyn Display, y: i32, text: &str) -> Result<(), MenuError> {
+    let text = format::fit(text, COLUMNS);
+    display.write(format::center(&text), y, &text, "6x8")
+}
+
+/// Draws `bitmap` with its top-left corner at `x` and `y`.
+fn draw(display: &mut dyn Display, bitmap: &Bitmap, x: i32, y: i32) -> Result<(), MenuError> {
+    display.draw(bitmap.bytes(), bitmap.width(), bitmap.height(), x, y)
+}
+
+/// The number of list entries shown on each page below the title.
+const PAGE_ROWS: usize = 6;
+
+/// Draws the number of the page containing the entry at index `selected`
+/// in the top-right corner and returns the index of its first entry.
+fn draw_page(display: &mut dyn Display, selected: usize, len: usize) -> Result<usize, MenuError> {
+    let page = selected / PAGE_ROWS;
+    let position = format!("{}/{}", page + 1, (len + PAGE_ROWS - 1) / PAGE_ROWS);
+    display.write(format::right(&position), 0, &position, "6x8")?;
+
+    Ok(page * PAGE_ROWS)
+}
+
+/// Shows the time in the large font. The clock moves every minute so that
+/// the same pixels are not lit for long.
+pub fn state_screensaver(display: &mut dyn Display, now: DateTime<Local>) -> Result<(), MenuError> {
+    let time = format!("{}", now.time().format("%H:%M"));
+    let minutes = now.timestamp() / 60;
+    let x = (minutes * 13).rem_euclid(display::WIDTH as i64 - 60 + 1);
+    let y = (minutes * 7).rem_euclid(display::HEIGHT as i64 - 16 + 1);
+
+    display.clear()?;
+    display.write(x as i32, y as i32, &time, "12x16")?;
+    display.flush()?;
+
+    Ok(())
+}
+
+pub fn state_buttons_offline(display: &mut dyn Display) -> Result<(), MenuError> {
+    display.clear()?;
+    display.write(21, 16, "BUTTONS OFFLINE", "6x8")?;
+    display.write(21, 27, "RECONNECTING...", "6x8")?;
+    display.flush()?;
+
+    Ok(())
+}
+
+pub fn state_reboot(display: &mut dyn Display, services: &dyn Services) -> Result<(), MenuError> {
+    display.clear()?;
+    display.write(27, 16, "REBOOTING", "6x8")?;
+    display.write(27, 27, "DEVICE...", "6x8")?;
+    display.flush()?;
 
     let three_secs = time::Duration::from_millis(3000);
     thread::sleep(three_secs);
 
-    oled_client::power(false)?;
-    info!("Rebooting device");
-    process::Command::new("sudo")
-        .arg("/sbin/shutdown")
-        .arg("-r")
-        .arg("now")
-        .output()
-        .expect("Failed to reboot");
+    display.power(false)?;
+    services.reboot()?;
 
     Ok(())
 }
 
-pub fn state_shutdown() -> Result<(), PeachError> {
-    oled_client::clear()?;
-    oled_client::write(27, 16, "SHUTTING", "6x8")?;
-    oled_client::write(27, 27, "DOWN", "6x8")?;
-    oled_client::write(27, 38, "DEVICE...", "6x8")?;
-    oled_client::flush()?;
+pub fn state_shutdown(display: &mut dyn Display, services: &dyn Services) -> Result<(), MenuError> {
+    display.clear()?;
+    display.write(27, 16, "SHUTTING", "6x8")?;
+    display.write(27, 27, "DOWN", "6x8")?;
+    display.write(27, 38, "DEVICE...", "6x8")?;
+    display.flush()?;
 
     let three_secs = time::Duration::from_millis(3000);
     thread::sleep(three_secs);
 
-    oled_client::power(false)?;
-    info!("Shutting down device");
-    process::Command::new("sudo")
-        .arg("/sbin/shutdown")
-        .arg("now")
-        .output()
-        .expect("Failed to shutdown");
+    display.power(false)?;
+    services.shutdown()?;
 
     Ok(())
 }
 
-pub fn state_stats() -> Result<(), PeachError> {
-    let cpu = stats_client::cpu_stats_percent()?;
+/// The number of recent rates drawn beside the current throughput.
+const RATE_HISTORY: u32 = 30;
+
+/// The height of each graph on the Stats graph page.
+const GRAPH_HEIGHT: u32 = 16;
+
+pub fn state_stats(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    monitor: &Monitor,
+    page: u8,
+) -> Result<(), MenuError> {
+    match page {
+        0 => stats_figures(display, services, monitor),
+        _ => stats_graphs(display, monitor),
+    }
+}
+
+/// Shows the current system statistics and throughput as text.
+fn stats_figures(
+    display: &mut dyn Display,
+    services: &dyn Services,
+    monitor: &Monitor,
+) -> Result<(), MenuError> {
+    let cpu = services.cpu_stats_percent()?;
     let cpu_stats = format!(
-        "CPU {} us {} sy {} id",
-        cpu.user.round(),
-        cpu.system.round(),
-        cpu.idle.round()
+        "CPU {}us {}sy {}id",
+        format::percent(cpu.user),
+        format::percent(cpu.system),
+        format::percent(cpu.idle)
     );
-    let mem = stats_client::mem_stats()?;
-    let mem_stats = format!("MEM {}MB f {}MB u", mem.free / 1024, mem.used / 1024);
-    let load = stats_client::load_average()?;
-    let load_stats = format!("LOAD {} {} {}", load.one, load.five, load.fifteen);
-    let uptime = stats_client::uptime()?;
-    let uptime_stats = format!("UPTIME {} mins", uptime);
-    let traffic = network_client::traffic("wlan0")?;
-    let rx = traffic.received / 1024 / 1024;
-    let rx_stats = format!("DATA RX {}MB", rx);
-    let tx = traffic.transmitted / 1024 / 1024;
-    let tx_stats = format!("DATA TX {}MB", tx);
-
-    oled_client::clear()?;
-    oled_client::write(0, 0, &cpu_stats, "6x8")?;
-    oled_client::write(0, 9, &mem_stats, "6x8")?;
-    oled_client::write(0, 18, &load_stats, "6x8")?;
-    oled_client::write(0, 27, &uptime_stats, "6x8")?;
-    oled_client::write(0, 36, &rx_stats, "6x8")?;
-    oled_client::write(0, 45, &tx_stats, "6x8")?;
-    oled_client::flush()?;
+    let mem = services.mem_stats()?;
+    let mem_stats = format!(
+        "MEM {}/{}",
+        format::bytes(mem.used * 1024),
+        format::bytes(mem.total * 1024)
+    );
+    let load = services.load_average()?;
+    let load_stats = format!("LOAD {:.2} {:.2} {:.2}", load.one, load.five, load.fifteen);
+    // uptime is reported in minutes
+    let uptime = services.uptime()?;
+    let uptime_stats = match uptime.trim().parse::<u64>() {
+        Ok(mins) => format!("UPTIME {}", format::duration(mins * 60)),
+        Err(_) => format!("UPTIME {} mins", uptime),
+    };
+    // rates are unknown until two samples have been taken
+    let (down, up) = match monitor.rate() {
+        Some(rate) => (format::rate(rate.received), format::rate(rate.transmitted)),
+        None => ("_".to_string(), "_".to_string()),
+    };
+
+    // leave room for the history beside the rates
+    let x = display::WIDTH - RATE_HISTORY;
+    let columns = COLUMNS - format::columns_from(x as i32);
+
+    display.clear()?;
+    write_line(display, 0, 0, &cpu_stats)?;
+    write_line(display, 0, 9, &mem_stats)?;
+    write_line(display, 0, 18, &load_stats)?;
+    write_line(display, 0, 27, &uptime_stats)?;
+    let down = format::fit(&format!("DOWN {}", down), columns);
+    display.write(0, 36, &down, "6x8")?;
+    draw(
+        display,
+        &monitor.received.bitmap(RATE_HISTORY, 8),
+        x as i32,
+        36,
+    )?;
+    let up = format::fit(&format!("UP {}", up), columns);
+    display.write(0, 45, &up, "6x8")?;
+    draw(
+        display,
+        &monitor.transmitted.bitmap(RATE_HISTORY, 8),
+        x as i32,
+        45,
+    )?;
+    display.flush()?;
+
+    Ok(())
+}
+
+/// Shows recent CPU usage, load and throughput as graphs, one per row with
+/// its name and latest value on the left.
+fn stats_graphs(display: &mut dyn Display, monitor: &Monitor) -> Result<(), MenuError> {
+    let none = || "_".to_string();
+    let graphs = [
+        (
+            "CPU",
+            monitor
+                .cpu
+                .last()
+                .map_or_else(none, |cpu| format::percent(cpu as f32)),
+            &monitor.cpu,
+        ),
+        (
+            "LOAD",
+            monitor
+                .load
+                .last()
+                .map_or_else(none, |load| format!("{:.2}", load as f32 / 100.0)),
+            &monitor.load,
+        ),
+        (
+            "DOWN /s",
+            monitor.received.last().map_or_else(none, format::bytes),
+            &monitor.received,
+        ),
+        (
+            "UP /s",
+            monitor.transmitted.last().map_or_else(none, format::bytes),
+            &monitor.transmitted,
+        ),
+    ];
+
+    // the labels take ten columns, the graphs the rest of the line
+    let x = 62;
+    let width = display::WIDTH - x as u32;
+
+    display.clear()?;
+    for (i, (name, value, graph)) in graphs.iter().enumerate() {
+        let y = GRAPH_HEIGHT as i32 * i as i32;
+        display.write(0, y, name, "6x8")?;
+        display.write(0, y + 8, &format::fit(value, 10), "6x8")?;
+        draw(display, &graph.bitmap(width, GRAPH_HEIGHT - 1), x, y)?;
+    }
+    display.flush()?;
 
     Ok(())
 }
 
+/// A 6x8 padlock, drawn next to secured networks.
+const LOCK_ICON: [u8; 8] = [0x30, 0x48, 0x48, 0xfc, 0xdc, 0xdc, 0xfc, 0x00];
+
 const PEACH_LOGO: [u8; 512] = [
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -16,7 +16,7 @@ pub struct CpuStatPercentages {
     pub nice: f32,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct DiskUsage {
     pub filesystem: Option<String>,
     pub one_k_blocks: u64,
@@ -40,7 +40,7 @@ pub struct MemStat {
     pub used: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize)]
 pub struct Traffic {
     pub received: u64,
     pub transmitted: u64,
@@ -51,3 +51,16 @@ pub struct Uptime {
     pub secs: u64,
     pub nanos: u32,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct Networks {
+    pub ssid: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Scan {
+    pub frequency: String,
+    pub protocol: String,
+    pub signal_level: String,
+    pub ssid: String,
+}
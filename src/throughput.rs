@@ -0,0 +1,138 @@
+//! Network throughput, computed from successive traffic counters.
+//!
+//! `peach-network` reports the total bytes received and transmitted by an
+//! interface since it came up. Throughput keeps the previous sample for each
+//! interface and turns the difference into bytes per second.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::structs::Traffic;
+
+/// Bytes per second received and transmitted by an interface.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rate {
+    pub received: u64,
+    pub transmitted: u64,
+}
+
+/// A traffic sample and the time it was taken.
+#[derive(Debug)]
+struct Sample {
+    traffic: Traffic,
+    taken: Instant,
+}
+
+/// The latest traffic sample and rate of each interface.
+#[derive(Debug, Default)]
+pub struct Throughput {
+    samples: HashMap<String, Sample>,
+    rates: HashMap<String, Rate>,
+}
+
+impl Throughput {
+    pub fn new() -> Throughput {
+        Throughput::default()
+    }
+
+    /// Records `traffic` for `iface`, taken at `taken`, and returns the rate
+    /// since the previous sample.
+    ///
+    /// No rate is returned for the first sample of an interface, or when its
+    /// counters have gone backwards because the interface was restarted.
+    pub fn record(&mut self, iface: &str, traffic: Traffic, taken: Instant) -> Option<Rate> {
+        let sample = Sample { traffic, taken };
+        let previous = self.samples.insert(iface.to_string(), sample)?;
+
+        let secs = taken.duration_since(previous.taken).as_secs_f64();
+        if secs <= 0.0
+            || traffic.received < previous.traffic.received
+            || traffic.transmitted < previous.traffic.transmitted
+        {
+            return None;
+        }
+        let per_sec = |now: u64, before: u64| ((now - before) as f64 / secs).round() as u64;
+        let rate = Rate {
+            received: per_sec(traffic.received, previous.traffic.received),
+            transmitted: per_sec(traffic.transmitted, previous.traffic.transmitted),
+        };
+
+        self.rates.insert(iface.to_string(), rate);
+
+        Some(rate)
+    }
+
+    /// Returns the most recent rate of `iface`.
+    pub fn rate(&self, iface: &str) -> Option<Rate> {
+        self.rates.get(iface).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn traffic(received: u64, transmitted: u64) -> Traffic {
+        Traffic {
+            received,
+            transmitted,
+        }
+    }
+
+    #[test]
+    fn first_sample_has_no_rate() {
+        let mut throughput = Throughput::new();
+        assert_eq!(
+            throughput.record("wlan0", traffic(100, 50), Instant::now()),
+            None
+        );
+        assert_eq!(throughput.rate("wlan0"), None);
+    }
+
+    #[test]
+    fn rate_is_per_second() {
+        let mut throughput = Throughput::new();
+        let start = Instant::now();
+        throughput.record("wlan0", traffic(1000, 500), start);
+        let rate = Rate {
+            received: 500,
+            transmitted: 250,
+        };
+        let later = start + Duration::from_secs(2);
+        assert_eq!(
+            throughput.record("wlan0", traffic(2000, 1000), later),
+            Some(rate)
+        );
+        assert_eq!(throughput.rate("wlan0"), Some(rate));
+        assert_eq!(throughput.rate("eth0"), None);
+    }
+
+    #[test]
+    fn restarted_counters_keep_the_last_rate() {
+        let mut throughput = Throughput::new();
+        let start = Instant::now();
+        throughput.record("wlan0", traffic(1000, 1000), start);
+        let rate = throughput.record("wlan0", traffic(2000, 2000), start + Duration::from_secs(1));
+        let restarted = start + Duration::from_secs(2);
+        assert_eq!(throughput.record("wlan0", traffic(10, 10), restarted), None);
+        assert_eq!(throughput.rate("wlan0"), rate);
+        let resumed = start + Duration::from_secs(3);
+        assert_eq!(
+            throughput.record("wlan0", traffic(110, 60), resumed),
+            Some(Rate {
+                received: 100,
+                transmitted: 50,
+            })
+        );
+    }
+
+    #[test]
+    fn samples_taken_together_have_no_rate() {
+        let mut throughput = Throughput::new();
+        let now = Instant::now();
+        throughput.record("wlan0", traffic(0, 0), now);
+        assert_eq!(throughput.record("wlan0", traffic(10, 10), now), None);
+    }
+}
@@ -0,0 +1,156 @@
+use std::cmp::Reverse;
+
+use log::warn;
+
+use crate::error::MenuError;
+use crate::services::Services;
+
+/// The wireless interface used for scanning and connecting in client mode.
+pub const WLAN_IFACE: &str = "wlan0";
+
+/// The shortest and longest WPA passphrases.
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+pub const MAX_PASSPHRASE_LEN: usize = 63;
+
+/// A wireless network found by a scan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessPoint {
+    pub ssid: String,
+    /// Signal strength in dBm.
+    pub signal: i32,
+    /// The network requires a password.
+    pub secured: bool,
+    /// Credentials for the network are saved in `peach-network`.
+    pub saved: bool,
+}
+
+/// Scans for wireless networks on `iface`.
+///
+/// Networks are returned strongest first. Hidden networks are skipped and
+/// networks seen on more than one channel are listed once, with the
+/// strongest signal.
+///
+/// # Arguments
+///
+/// * `services` - The services used to query `peach-network`.
+/// * `iface` - The wireless interface to scan with.
+///
+pub fn scan(services: &dyn Services, iface: &str) -> Result<Vec<AccessPoint>, MenuError> {
+    let saved = services.saved_networks()?;
+    let mut networks: Vec<AccessPoint> = Vec::new();
+    for result in services.available_networks(iface)? {
+        if result.ssid.is_empty() {
+            continue;
+        }
+        let signal = result.signal_level.trim().parse().unwrap_or(i32::MIN);
+        match networks.iter_mut().find(|ap| ap.ssid == result.ssid) {
+            Some(ap) => ap.signal = ap.signal.max(signal),
+            None => networks.push(AccessPoint {
+                saved: saved.contains(&result.ssid),
+                secured: result.protocol.contains("WPA") || result.protocol.contains("WEP"),
+                ssid: result.ssid,
+                signal,
+            }),
+        }
+    }
+    networks.sort_by_key(|ap| Reverse(ap.signal));
+
+    Ok(networks)
+}
+
+/// A wireless network with credentials saved in `peach-network`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedNetwork {
+    pub ssid: String,
+    /// `iface` is currently connected to the network.
+    pub connected: bool,
+}
+
+/// Returns the saved networks, in the order in which they are preferred.
+/// Networks whose priority cannot be read are taken to have priority 0.
+///
+/// # Arguments
+///
+/// * `services` - The services used to query `peach-network`.
+/// * `iface` - The wireless interface the networks are saved for.
+///
+pub fn saved(services: &dyn Services, iface: &str) -> Result<Vec<SavedNetwork>, MenuError> {
+    // not being connected is reported as an error
+    let current = services.ssid(iface).ok();
+    let mut networks = Vec::new();
+    for ssid in services.saved_networks()? {
+        let priority = services.priority(&ssid, iface).unwrap_or_else(|err| {
+            warn!("Failed to read the priority of {}: {:?}", ssid, err);
+            0
+        });
+        networks.push((priority, ssid));
+    }
+    // networks with equal priority keep the order in which they were saved
+    networks.sort_by_key(|&(priority, _)| Reverse(priority));
+
+    Ok(networks
+        .into_iter()
+        .map(|(_, ssid)| SavedNetwork {
+            connected: current.as_ref() == Some(&ssid),
+            ssid,
+        })
+        .collect())
+}
+
+/// Sets the priority of the saved networks so that they are preferred in the
+/// order given.
+///
+/// # Arguments
+///
+/// * `services` - The services used to update `wpa_supplicant`.
+/// * `iface` - The wireless interface the networks are saved for.
+/// * `networks` - The saved networks, most preferred first.
+///
+pub fn prioritise(
+    services: &dyn Services,
+    iface: &str,
+    networks: &[SavedNetwork],
+) -> Result<(), MenuError> {
+    for (i, network) in networks.iter().enumerate() {
+        let priority = (networks.len() - i) as i32;
+        services.set_priority(&network.ssid, iface, priority)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::Simulated;
+
+    #[test]
+    fn scan_lists_each_visible_network_once_strongest_first() {
+        let networks = scan(&Simulated::new(), WLAN_IFACE).unwrap();
+        let ssids: Vec<&str> = networks.iter().map(|ap| ap.ssid.as_str()).collect();
+        assert_eq!(
+            ssids,
+            vec![
+                "PeachNet",
+                "Library",
+                "Cafe Guest",
+                "Workshop",
+                "Neighbour 5G",
+                "Studio",
+                "FreeWifi"
+            ]
+        );
+        // PeachNet is seen at -52 and -64 dBm and keeps the stronger signal
+        assert_eq!(networks[0].signal - networks[1].signal, 15);
+    }
+
+    #[test]
+    fn scan_marks_secured_and_saved_networks() {
+        let networks = scan(&Simulated::new(), WLAN_IFACE).unwrap();
+        let find = |ssid: &str| networks.iter().find(|ap| ap.ssid == ssid).unwrap();
+        assert!(find("PeachNet").secured && find("PeachNet").saved);
+        assert!(!find("Cafe Guest").secured && find("Cafe Guest").saved);
+        assert!(find("Studio").secured && !find("Studio").saved);
+        assert!(!find("FreeWifi").secured);
+    }
+}